@@ -0,0 +1,170 @@
+use criterion::*;
+use fastwebsockets::FragmentCollector;
+use fastwebsockets::Frame;
+use fastwebsockets::OpCode;
+use fastwebsockets::Role;
+use fastwebsockets::WebSocket;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::io::ReadBuf;
+
+const SIZES: [(usize, &str); 4] = [
+  (64, "64 B"),
+  (1024, "1 KB"),
+  (64 << 10, "64 KB"),
+  (1 << 20, "1 MB"),
+];
+
+const FRAMES_PER_READ: usize = 64;
+
+/// A stream that discards everything written to it and never yields data.
+struct Sink;
+
+impl AsyncRead for Sink {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+    _buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Pending
+  }
+}
+
+impl AsyncWrite for Sink {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    Poll::Ready(Ok(buf.len()))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+  tokio::runtime::Builder::new_current_thread()
+    .build()
+    .unwrap()
+}
+
+/// Serializes `frames` into their wire representation.
+fn encode(frames: Vec<Frame>) -> Vec<u8> {
+  let mut buf = Vec::new();
+  let mut out = Vec::new();
+  for mut frame in frames {
+    out.extend_from_slice(frame.write(&mut buf));
+  }
+  out
+}
+
+fn write_frame(c: &mut Criterion) {
+  let rt = runtime();
+
+  for opcode in [OpCode::Text, OpCode::Binary] {
+    let mut group = c.benchmark_group(format!("write_frame {:?}", opcode));
+    for (size, name) in SIZES {
+      let payload = vec![b'a'; size];
+      let mut ws = WebSocket::after_handshake(Sink, Role::Server);
+
+      // One iteration writes one frame.
+      group.throughput(Throughput::Bytes(size as u64));
+      group.bench_function(name, |b| {
+        b.iter(|| {
+          let frame = Frame::new(true, opcode, None, payload[..].into());
+          rt.block_on(ws.write_frame(black_box(frame))).unwrap();
+        });
+      });
+    }
+    group.finish();
+  }
+}
+
+fn read_frame(c: &mut Criterion) {
+  let rt = runtime();
+
+  let mut group = c.benchmark_group("read_frame");
+  for (size, name) in SIZES {
+    let payload = vec![b'a'; size];
+    let data = encode(
+      (0..FRAMES_PER_READ)
+        .map(|_| Frame::binary(payload[..].into()))
+        .collect(),
+    );
+
+    group.throughput(Throughput::Bytes((size * FRAMES_PER_READ) as u64));
+    group.bench_function(name, |b| {
+      b.iter(|| {
+        rt.block_on(async {
+          let (mut tx, rx) = tokio::io::duplex(64 << 10);
+          let data = data.clone();
+          tokio::spawn(async move { tx.write_all(&data).await.unwrap() });
+
+          let mut ws = WebSocket::after_handshake(rx, Role::Client);
+          for _ in 0..FRAMES_PER_READ {
+            black_box(ws.read_frame().await.unwrap());
+          }
+        })
+      });
+    });
+  }
+  group.finish();
+}
+
+fn fragment_collector(c: &mut Criterion) {
+  const FRAGMENT_SIZE: usize = 1024;
+
+  let rt = runtime();
+
+  let mut group = c.benchmark_group("FragmentCollector");
+  for fragments in [10, 100, 1000] {
+    let payload = vec![b'a'; FRAGMENT_SIZE];
+    let data = encode(
+      (0..fragments)
+        .map(|i| {
+          let opcode = if i == 0 {
+            OpCode::Binary
+          } else {
+            OpCode::Continuation
+          };
+          Frame::new(i == fragments - 1, opcode, None, payload[..].into())
+        })
+        .collect(),
+    );
+
+    group.throughput(Throughput::Bytes((FRAGMENT_SIZE * fragments) as u64));
+    group.bench_function(format!("{} fragments", fragments), |b| {
+      b.iter(|| {
+        rt.block_on(async {
+          let (mut tx, rx) = tokio::io::duplex(64 << 10);
+          let data = data.clone();
+          tokio::spawn(async move { tx.write_all(&data).await.unwrap() });
+
+          let ws = WebSocket::after_handshake(rx, Role::Client);
+          let mut ws = FragmentCollector::new(ws);
+          black_box(ws.read_frame().await.unwrap());
+        })
+      });
+    });
+  }
+  group.finish();
+}
+
+criterion_group!(benches, write_frame, read_frame, fragment_collector);
+criterion_main!(benches);
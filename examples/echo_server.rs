@@ -62,7 +62,7 @@ fn main() -> Result<(), WebSocketError> {
 
   rt.block_on(async move {
     let listener = TcpListener::bind("127.0.0.1:8080").await?;
-    println!("Server started, listening on {}", "127.0.0.1:8080");
+    println!("Server started, listening on 127.0.0.1:8080");
     loop {
       let (stream, _) = listener.accept().await?;
       println!("Client connected");
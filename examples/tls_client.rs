@@ -32,15 +32,15 @@ where
 fn tls_connector() -> Result<TlsConnector> {
   let mut root_store = tokio_rustls::rustls::RootCertStore::empty();
 
-  root_store.add_server_trust_anchors(
-    webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+  root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(
+    |ta| {
       OwnedTrustAnchor::from_subject_spki_name_constraints(
         ta.subject,
         ta.spki,
         ta.name_constraints,
       )
-    }),
-  );
+    },
+  ));
 
   let config = ClientConfig::builder()
     .with_safe_defaults()
@@ -85,7 +85,7 @@ fn tls_acceptor() -> Result<TlsAcceptor> {
 async fn main() -> Result<()> {
   let acceptor = tls_acceptor()?;
   let listener = TcpListener::bind("127.0.0.1:8080").await?;
-  println!("Server started, listening on {}", "127.0.0.1:8080");
+  println!("Server started, listening on 127.0.0.1:8080");
   loop {
     let (stream, _) = listener.accept().await?;
     println!("Client connected");
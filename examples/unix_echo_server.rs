@@ -0,0 +1,68 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local IPC echo server over a Unix domain socket.
+//!
+//! There is no HTTP upgrade: clients connect to the socket and start sending
+//! WebSocket frames immediately.
+//!
+//! ```text
+//! cargo run --example unix_echo_server --features unix -- /tmp/fastwebsockets.sock
+//! ```
+
+use fastwebsockets::FragmentCollector;
+use fastwebsockets::OpCode;
+use fastwebsockets::Role;
+use fastwebsockets::WebSocket;
+use fastwebsockets::WebSocketError;
+use tokio::net::UnixListener;
+use tokio::net::UnixStream;
+
+async fn handle_client(stream: UnixStream) -> Result<(), WebSocketError> {
+  let ws = WebSocket::over_unix(stream, Role::Server);
+  let mut ws = FragmentCollector::new(ws);
+
+  loop {
+    let frame = ws.read_frame().await?;
+    match frame.opcode {
+      OpCode::Close => break,
+      OpCode::Text | OpCode::Binary => {
+        ws.write_frame(frame).await?;
+      }
+      _ => {}
+    }
+  }
+
+  Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), WebSocketError> {
+  let path = std::env::args()
+    .nth(1)
+    .unwrap_or_else(|| "/tmp/fastwebsockets.sock".to_string());
+  let _ = std::fs::remove_file(&path);
+
+  let listener = UnixListener::bind(&path)?;
+  println!("Server started, listening on {}", path);
+  loop {
+    let (stream, _) = listener.accept().await?;
+    println!("Client connected");
+    tokio::spawn(async move {
+      if let Err(e) = handle_client(stream).await {
+        eprintln!("Error in websocket connection: {}", e);
+      }
+    });
+  }
+}
@@ -0,0 +1,215 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::task::Poll;
+
+use tokio::io::AsyncWrite;
+
+use crate::Frame;
+use crate::OpCode;
+use crate::Role;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+/// Sends the same frame to a group of server-side WebSocket connections.
+///
+/// The frame is serialized once and the resulting bytes are written to every connection concurrently.
+/// Connections that fail to receive a frame are removed from the group.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{BroadcastGroup, Frame, WebSocket};
+/// use tokio::net::TcpStream;
+///
+/// async fn notify(group: &mut BroadcastGroup<TcpStream>) {
+///   let errors = group.broadcast(Frame::text(b"update"[..].into())).await;
+///   for e in errors {
+///     eprintln!("Dropped connection: {}", e);
+///   }
+/// }
+/// ```
+pub struct BroadcastGroup<S> {
+  sockets: Vec<WebSocket<S>>,
+  write_buffer: Vec<u8>,
+}
+
+impl<S> Default for BroadcastGroup<S> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<S> BroadcastGroup<S> {
+  /// Creates an empty `BroadcastGroup`.
+  pub fn new() -> Self {
+    Self {
+      sockets: Vec::new(),
+      write_buffer: Vec::with_capacity(2),
+    }
+  }
+
+  /// Adds a connection to the group.
+  ///
+  /// # Panics
+  ///
+  /// Frames are serialized once for the whole group and are never masked, so this
+  /// method panics if `ws` is a client-side connection.
+  pub fn add(&mut self, ws: WebSocket<S>) {
+    assert!(
+      ws.write_half.role == Role::Server,
+      "BroadcastGroup only supports server-side connections"
+    );
+    self.sockets.push(ws);
+  }
+
+  /// Returns the number of connections in the group.
+  pub fn len(&self) -> usize {
+    self.sockets.len()
+  }
+
+  /// Returns `true` if the group has no connections.
+  pub fn is_empty(&self) -> bool {
+    self.sockets.is_empty()
+  }
+
+  /// Consumes the `BroadcastGroup` and returns the connections in it.
+  pub fn into_inner(self) -> Vec<WebSocket<S>> {
+    self.sockets
+  }
+
+  /// Writes `frame` to every connection in the group.
+  ///
+  /// Connections that fail are removed from the group and their errors are returned. A connection
+  /// that has already sent a close frame fails with [`WebSocketError::ConnectionClosed`].
+  ///
+  /// Before the frame, each connection's own unwritten bytes are flushed: the rest of a frame whose
+  /// write was cancelled, and queued control frames such as automatic pongs. This happens
+  /// concurrently with the writes to the other connections, so a stalled peer does not hold them up.
+  ///
+  /// Since the frame is serialized once for the whole group, it bypasses the write extension and the
+  /// write rate limit of every connection.
+  ///
+  /// This method is not cancel safe: dropping the future may leave partially written frames.
+  pub async fn broadcast(&mut self, mut frame: Frame<'_>) -> Vec<WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    let bytes = frame.write(&mut self.write_buffer);
+    let sockets = &mut self.sockets;
+
+    let mut written = vec![0; sockets.len()];
+    let mut errors: Vec<Option<WebSocketError>> =
+      sockets.iter().map(|_| None).collect();
+
+    for (ws, error) in sockets.iter_mut().zip(errors.iter_mut()) {
+      if frame.opcode == OpCode::Close {
+        ws.write_half.closed = true;
+      } else if ws.write_half.closed {
+        *error = Some(WebSocketError::ConnectionClosed);
+      }
+    }
+    let mut flushed = vec![false; sockets.len()];
+
+    poll_fn(|cx| {
+      let mut pending = false;
+      for (i, ws) in sockets.iter_mut().enumerate() {
+        if errors[i].is_none() && !flushed[i] {
+          match ws.write_half.poll_flush_pending(&mut ws.stream, cx) {
+            Poll::Ready(Ok(())) => flushed[i] = true,
+            Poll::Ready(Err(e)) => errors[i] = Some(e),
+            Poll::Pending => {
+              pending = true;
+              continue;
+            }
+          }
+        }
+        while errors[i].is_none() && written[i] < bytes.len() {
+          match Pin::new(&mut ws.stream).poll_write(cx, &bytes[written[i]..]) {
+            Poll::Ready(Ok(0)) => {
+              errors[i] = Some(
+                std::io::Error::from(std::io::ErrorKind::WriteZero).into(),
+              );
+            }
+            Poll::Ready(Ok(n)) => written[i] += n,
+            Poll::Ready(Err(e)) => errors[i] = Some(e.into()),
+            Poll::Pending => {
+              pending = true;
+              break;
+            }
+          }
+        }
+      }
+
+      if pending {
+        Poll::Pending
+      } else {
+        Poll::Ready(())
+      }
+    })
+    .await;
+
+    let mut failed = errors.iter().map(Option::is_some);
+    sockets.retain(|_| !failed.next().unwrap());
+    errors.into_iter().flatten().collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn broadcast_flushes_queued_frames() {
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::default(),
+      Role::Server,
+    );
+    let pong = Frame::pong(b"queued"[..].into()).to_vec();
+    ws.write_half.priority_write_buffer = pong.clone();
+
+    let mut group = BroadcastGroup::new();
+    group.add(ws);
+    let errors = group.broadcast(Frame::text(b"update"[..].into())).await;
+    assert!(errors.is_empty());
+
+    let ws = group.into_inner().pop().unwrap();
+    let mut expected = pong;
+    expected.extend(Frame::text(b"update"[..].into()).to_vec());
+    assert_eq!(ws.into_inner().written(), expected);
+  }
+
+  #[tokio::test]
+  async fn broadcast_stalled_peer() {
+    let (stalled, _peer) = tokio::io::duplex(16);
+    let mut stalled = WebSocket::after_handshake(stalled, Role::Server);
+    stalled.write_half.priority_write_buffer = vec![0; 1024];
+    let (server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+
+    let mut group = BroadcastGroup::new();
+    group.add(stalled);
+    group.add(server);
+    let broadcast = group.broadcast(Frame::text(b"update"[..].into()));
+    assert!(tokio::time::timeout(
+      std::time::Duration::from_millis(10),
+      broadcast
+    )
+    .await
+    .is_err());
+    assert_eq!(client.read_frame().await.unwrap().payload, b"update");
+  }
+}
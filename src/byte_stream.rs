@@ -0,0 +1,259 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::pin::pin;
+use std::pin::Pin;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+
+use bytes::Buf;
+use bytes::BytesMut;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+use crate::Frame;
+use crate::OpCode;
+use crate::Payload;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+fn into_io_error(e: WebSocketError) -> std::io::Error {
+  match e {
+    WebSocketError::IoError(e) => e,
+    WebSocketError::UnexpectedEOF => std::io::ErrorKind::UnexpectedEof.into(),
+    e => std::io::Error::new(std::io::ErrorKind::Other, e),
+  }
+}
+
+/// A WebSocket connection used as a plain byte pipe, e.g. to tunnel another protocol.
+///
+/// Reading yields the payloads of received data frames back to back, regardless of how the peer
+/// split them into frames or messages. Unconsumed bytes of a payload are kept for the next read.
+/// A close frame from the peer is reported as EOF. Every write is sent as a single binary frame,
+/// and shutting down the writer sends a close frame.
+///
+/// Pings and close frames are answered as configured on the `WebSocket`; the replies are queued
+/// behind any frame that is still being written.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{WebSocket, WebSocketByteStream};
+/// use tokio::net::TcpStream;
+///
+/// async fn tunnel(ws: WebSocket<TcpStream>, mut upstream: TcpStream) -> std::io::Result<()> {
+///   let mut ws = WebSocketByteStream::new(ws);
+///   tokio::io::copy_bidirectional(&mut ws, &mut upstream).await?;
+///   Ok(())
+/// }
+/// ```
+pub struct WebSocketByteStream<S> {
+  ws: WebSocket<S>,
+  read_buffer: BytesMut,
+  eof: bool,
+}
+
+impl<S> WebSocketByteStream<S> {
+  pub fn new(ws: WebSocket<S>) -> Self {
+    Self {
+      ws,
+      read_buffer: BytesMut::new(),
+      eof: false,
+    }
+  }
+
+  /// Consumes the `WebSocketByteStream` and returns the `WebSocket`. Buffered payload bytes that
+  /// have not been read and frames that have not been written yet are discarded.
+  pub fn into_inner(self) -> WebSocket<S> {
+    self.ws
+  }
+
+  /// Serializes `frame` behind the frames that are already pending.
+  fn queue_frame(&mut self, frame: Frame) -> Result<(), WebSocketError> {
+    self.ws.write_half.queue_frame(frame)
+  }
+
+  fn poll_write_pending(
+    &mut self,
+    cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    self
+      .ws
+      .write_half
+      .poll_flush_pending(&mut self.ws.stream, cx)
+      .map_err(into_io_error)
+  }
+}
+
+impl<S> AsyncRead for WebSocketByteStream<S>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let this = self.get_mut();
+    // Make progress on queued replies even if the application never writes.
+    if let Poll::Ready(Err(e)) = this.poll_write_pending(cx) {
+      return Poll::Ready(Err(e));
+    }
+
+    while this.read_buffer.is_empty() && !this.eof {
+      // Reading frames is cancel safe, so a pending read can be dropped and started again on the
+      // next poll without losing data.
+      let (res, obligated_send) = {
+        let read = this.ws.read_half.read_frame_inner(&mut this.ws.stream);
+        ready!(pin!(read).poll(cx))
+      };
+      if let Some(frame) = obligated_send {
+        if !this.ws.write_half.closed {
+          this.queue_frame(frame).map_err(into_io_error)?;
+          if let Poll::Ready(Err(e)) = this.poll_write_pending(cx) {
+            return Poll::Ready(Err(e));
+          }
+        }
+      }
+
+      let Some(frame) = res.map_err(into_io_error)? else {
+        continue;
+      };
+      match frame.opcode {
+        OpCode::Close => this.eof = true,
+        OpCode::Text | OpCode::Binary | OpCode::Continuation => {
+          this.read_buffer = match frame.payload {
+            Payload::Bytes(payload) => payload,
+            payload => BytesMut::from(&payload[..]),
+          };
+        }
+        OpCode::Ping | OpCode::Pong => {}
+      }
+    }
+
+    let n = this.read_buffer.len().min(buf.remaining());
+    buf.put_slice(&this.read_buffer[..n]);
+    this.read_buffer.advance(n);
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl<S> AsyncWrite for WebSocketByteStream<S>
+where
+  S: AsyncWrite + Unpin,
+{
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    let this = self.get_mut();
+    ready!(this.poll_write_pending(cx))?;
+    if buf.is_empty() {
+      return Poll::Ready(Ok(0));
+    }
+
+    this
+      .queue_frame(Frame::binary(Payload::Borrowed(buf)))
+      .map_err(into_io_error)?;
+    // The frame is accepted once it is queued; whatever is not written now is written by the next call.
+    if let Poll::Ready(Err(e)) = this.poll_write_pending(cx) {
+      return Poll::Ready(Err(e));
+    }
+    Poll::Ready(Ok(buf.len()))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let this = self.get_mut();
+    ready!(this.poll_write_pending(cx))?;
+    Pin::new(&mut this.ws.stream).poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let this = self.get_mut();
+    if !this.ws.write_half.closed {
+      this
+        .queue_frame(Frame::close(1000, b""))
+        .map_err(into_io_error)?;
+    }
+    ready!(this.poll_write_pending(cx))?;
+    Pin::new(&mut this.ws.stream).poll_shutdown(cx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tokio::io::AsyncReadExt;
+  use tokio::io::AsyncWriteExt;
+
+  use super::*;
+  use crate::test_utils::server_with_frames;
+
+  #[tokio::test]
+  async fn byte_stream() {
+    let (server, mut client) = server_with_frames([
+      Frame::new(false, OpCode::Text, None, b"hel"[..].into()),
+      Frame::new(true, OpCode::Ping, None, b"p"[..].into()),
+      Frame::new(true, OpCode::Continuation, None, b"lo"[..].into()),
+    ])
+    .await;
+    let mut stream = WebSocketByteStream::new(server);
+
+    let mut data = [0; 5];
+    stream.read_exact(&mut data).await.unwrap();
+    assert_eq!(&data, b"hello");
+
+    stream.write_all(b"world").await.unwrap();
+    let pong = client.read_frame().await.unwrap();
+    assert_eq!(pong.opcode, OpCode::Pong);
+    let frame = client.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Binary);
+    assert_eq!(frame.payload, b"world");
+
+    // The peer's close frame is EOF and is answered automatically.
+    client.write_frame(Frame::close(1000, b"")).await.unwrap();
+    assert_eq!(stream.read(&mut data).await.unwrap(), 0);
+    stream.shutdown().await.unwrap();
+    let close = client.read_frame().await.unwrap();
+    assert_eq!(close.opcode, OpCode::Close);
+  }
+
+  #[tokio::test]
+  async fn into_async_read_write() {
+    let (server, mut client) =
+      server_with_frames([Frame::binary(b"ping"[..].into())]).await;
+    let mut stream = server.into_async_read_write();
+
+    let mut data = [0; 4];
+    stream.read_exact(&mut data).await.unwrap();
+    assert_eq!(&data, b"ping");
+
+    stream.write_all(b"pong").await.unwrap();
+    let frame = client.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Binary);
+    assert_eq!(frame.payload, b"pong");
+  }
+}
@@ -19,8 +19,11 @@
 // limitations under the License.
 
 use self::CloseCode::*;
+use crate::WebSocketError;
+
 /// Status code used to indicate why an endpoint is closing the WebSocket connection.
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[non_exhaustive]
 pub enum CloseCode {
   /// Indicates a normal closure, meaning that the purpose for
   /// which the connection was established has been fulfilled.
@@ -149,3 +152,82 @@ impl From<CloseCode> for u16 {
     }
   }
 }
+
+/// The parsed payload of a close frame, see
+/// [`Frame::into_close_frame`](crate::Frame::into_close_frame).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseFrame {
+  /// The status code, [`CloseCode::Status`] if the frame had no payload.
+  pub code: CloseCode,
+  /// The reason, empty if the frame had none.
+  pub reason: String,
+}
+
+/// Parses the payload of a close frame into its status code and reason.
+///
+/// An empty payload has no status code and is returned as [`CloseCode::Status`] with an empty
+/// reason, and a 2-byte payload has a status code but no reason. A 1-byte payload fails with
+/// [`WebSocketError::InvalidCloseFrame`] and a reason that is not valid UTF-8 with
+/// [`WebSocketError::InvalidUTF8`]. The status code itself is not checked, see
+/// [`CloseCode::is_allowed`].
+pub fn parse_close_payload(
+  payload: &[u8],
+) -> Result<(CloseCode, &str), WebSocketError> {
+  match payload.len() {
+    0 => Ok((Status, "")),
+    1 => Err(WebSocketError::InvalidCloseFrame),
+    _ => {
+      let code = CloseCode::from(u16::from_be_bytes([payload[0], payload[1]]));
+      let reason = std::str::from_utf8(&payload[2..])
+        .map_err(|_| WebSocketError::InvalidUTF8)?;
+      Ok((code, reason))
+    }
+  }
+}
+
+/// Encodes a status code and reason as the payload of a close frame. The reverse of
+/// [`parse_close_payload`]: [`CloseCode::Status`] is encoded as an empty payload and the reason is
+/// dropped.
+pub fn encode_close_payload(code: CloseCode, reason: &str) -> Vec<u8> {
+  if code == Status {
+    return Vec::new();
+  }
+  let mut payload = Vec::with_capacity(2 + reason.len());
+  payload.extend_from_slice(&u16::from(code).to_be_bytes());
+  payload.extend_from_slice(reason.as_bytes());
+  payload
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_close_payload_lengths() {
+    assert_eq!(parse_close_payload(b"").unwrap(), (Status, ""));
+    assert!(matches!(
+      parse_close_payload(b"\x03"),
+      Err(WebSocketError::InvalidCloseFrame)
+    ));
+    assert_eq!(parse_close_payload(b"\x03\xe8").unwrap(), (Normal, ""));
+    assert_eq!(parse_close_payload(b"\x03\xe9bye").unwrap(), (Away, "bye"));
+    assert_eq!(
+      parse_close_payload(b"\x0f\xa0x").unwrap(),
+      (Library(4000), "x")
+    );
+    assert!(matches!(
+      parse_close_payload(b"\x03\xe8\xff"),
+      Err(WebSocketError::InvalidUTF8)
+    ));
+  }
+
+  #[test]
+  fn encode_close_payload_roundtrip() {
+    assert!(encode_close_payload(Status, "ignored").is_empty());
+    assert_eq!(encode_close_payload(Normal, ""), b"\x03\xe8");
+    for (code, reason) in [(Normal, ""), (Away, "bye"), (Iana(3001), "later")] {
+      let payload = encode_close_payload(code, reason);
+      assert_eq!(parse_close_payload(&payload).unwrap(), (code, reason));
+    }
+  }
+}
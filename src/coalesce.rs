@@ -0,0 +1,189 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+use tokio::time::Instant;
+
+use crate::Frame;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+/// Buffers outgoing frames and writes them together, trading latency for fewer writes when many
+/// small frames are sent.
+///
+/// Buffered frames are written once they reach `max_coalesced_bytes`, when a frame is written after
+/// `flush_interval` has passed since the oldest buffered frame, or on an explicit
+/// [`CoalescingWriter::flush`]. Since nothing is written while no frames arrive, a writer that can
+/// be idle should also await [`CoalescingWriter::flush_when_due`].
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{CoalescingWriter, Frame, WebSocket, WebSocketError};
+/// use std::time::Duration;
+/// use tokio::net::TcpStream;
+/// use tokio::sync::mpsc;
+///
+/// async fn forward(
+///   ws: WebSocket<TcpStream>,
+///   mut updates: mpsc::Receiver<Vec<u8>>,
+/// ) -> Result<(), WebSocketError> {
+///   let mut ws = CoalescingWriter::new(ws, 16 * 1024, Duration::from_millis(10));
+///   loop {
+///     tokio::select! {
+///       update = updates.recv() => match update {
+///         Some(update) => ws.write_frame_coalesced(Frame::binary(update.into())).await?,
+///         None => break,
+///       },
+///       res = ws.flush_when_due() => res?,
+///     }
+///   }
+///   ws.flush().await
+/// }
+/// ```
+pub struct CoalescingWriter<S> {
+  ws: WebSocket<S>,
+  buffer: Vec<u8>,
+  written: usize,
+  max_coalesced_bytes: usize,
+  flush_interval: Duration,
+  deadline: Option<Instant>,
+}
+
+impl<S> CoalescingWriter<S> {
+  pub fn new(
+    ws: WebSocket<S>,
+    max_coalesced_bytes: usize,
+    flush_interval: Duration,
+  ) -> Self {
+    Self {
+      ws,
+      buffer: Vec::new(),
+      written: 0,
+      max_coalesced_bytes,
+      flush_interval,
+      deadline: None,
+    }
+  }
+
+  /// Returns the number of buffered bytes that have not been written yet.
+  pub fn buffered(&self) -> usize {
+    self.buffer.len() - self.written
+  }
+
+  /// Consumes the `CoalescingWriter` and returns the `WebSocket`. Buffered frames are discarded, so
+  /// call [`CoalescingWriter::flush`] first.
+  pub fn into_inner(self) -> WebSocket<S> {
+    self.ws
+  }
+
+  /// Buffers `frame`, writing the buffer if it is full or due.
+  pub async fn write_frame_coalesced(
+    &mut self,
+    mut frame: Frame<'_>,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    self.ws.write_half.prepare_frame(&mut frame)?;
+    self.buffer.extend_from_slice(&frame.to_vec());
+
+    let now = Instant::now();
+    let deadline = *self.deadline.get_or_insert(now + self.flush_interval);
+    if self.buffered() >= self.max_coalesced_bytes || now >= deadline {
+      self.flush().await?;
+    }
+    Ok(())
+  }
+
+  /// Waits until the oldest buffered frame has been buffered for `flush_interval`, then writes the
+  /// buffer. Never completes while nothing is buffered.
+  ///
+  /// This method is cancel safe, so it can be used in `tokio::select!`.
+  pub async fn flush_when_due(&mut self) -> Result<(), WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    match self.deadline {
+      Some(deadline) => tokio::time::sleep_until(deadline).await,
+      None => std::future::pending().await,
+    }
+    self.flush().await
+  }
+
+  /// Writes all buffered frames and flushes the stream.
+  ///
+  /// If the future is dropped, the unwritten part of the buffer is written by the next flush.
+  pub async fn flush(&mut self) -> Result<(), WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    // Frames queued by the `WebSocket` itself go first, as they may be partially written.
+    self
+      .ws
+      .write_half
+      .flush_pending(&mut self.ws.stream)
+      .await?;
+    while self.written < self.buffer.len() {
+      let n = self.ws.stream.write(&self.buffer[self.written..]).await?;
+      if n == 0 {
+        return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+      }
+      self.written += n;
+    }
+    self.buffer.clear();
+    self.written = 0;
+    self.deadline = None;
+    self.ws.stream.flush().await?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Role;
+
+  #[tokio::test(start_paused = true)]
+  async fn coalescing_writer() {
+    let ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::default(),
+      Role::Server,
+    );
+    let mut ws =
+      CoalescingWriter::new(ws, 15, std::time::Duration::from_millis(10));
+
+    let frame = || Frame::text(b"abc"[..].into());
+    ws.write_frame_coalesced(frame()).await.unwrap();
+    ws.write_frame_coalesced(frame()).await.unwrap();
+    assert_eq!(ws.buffered(), 10);
+
+    // Full buffer.
+    ws.write_frame_coalesced(frame()).await.unwrap();
+    assert_eq!(ws.buffered(), 0);
+
+    // Due buffer.
+    ws.write_frame_coalesced(frame()).await.unwrap();
+    let start = tokio::time::Instant::now();
+    ws.flush_when_due().await.unwrap();
+    assert_eq!(start.elapsed(), std::time::Duration::from_millis(10));
+    assert_eq!(ws.buffered(), 0);
+
+    let written = ws.into_inner().into_inner().written().to_vec();
+    assert_eq!(written, frame().to_vec().repeat(4));
+  }
+}
@@ -0,0 +1,161 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+use crate::Role;
+use crate::WebSocket;
+
+/// The largest payload that fits in a single UDP datagram.
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// A connected, datagram-oriented socket.
+///
+/// Implemented for a [`tokio::net::UdpSocket`] that has been `connect`ed to its peer.
+pub trait AsyncDatagramSocket {
+  fn poll_recv(
+    &self,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>>;
+
+  fn poll_send(
+    &self,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>>;
+}
+
+impl AsyncDatagramSocket for tokio::net::UdpSocket {
+  fn poll_recv(
+    &self,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    tokio::net::UdpSocket::poll_recv(self, cx, buf)
+  }
+
+  fn poll_send(
+    &self,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    tokio::net::UdpSocket::poll_send(self, cx, buf)
+  }
+}
+
+/// Presents a datagram socket as a byte stream.
+///
+/// Every write is sent as one datagram and received datagrams are concatenated on read. This is
+/// **not** a standard WebSocket transport: datagrams can be lost, duplicated or reordered, and any of
+/// these corrupts the frame stream. Only use it on links where that cannot happen or where the
+/// connection can simply be re-established.
+pub struct DatagramAdapter<D> {
+  socket: D,
+  buffer: Box<[u8]>,
+  pos: usize,
+  len: usize,
+}
+
+impl<D> DatagramAdapter<D> {
+  pub fn new(socket: D) -> Self {
+    Self {
+      socket,
+      buffer: vec![0; MAX_DATAGRAM_SIZE].into_boxed_slice(),
+      pos: 0,
+      len: 0,
+    }
+  }
+
+  /// Consumes the `DatagramAdapter` and returns the underlying socket.
+  pub fn into_inner(self) -> D {
+    self.socket
+  }
+}
+
+impl<D> AsyncRead for DatagramAdapter<D>
+where
+  D: AsyncDatagramSocket + Unpin,
+{
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let this = self.get_mut();
+    // Empty datagrams carry no stream data, keep waiting instead of reporting EOF.
+    while this.pos == this.len {
+      let mut datagram = ReadBuf::new(&mut this.buffer);
+      ready!(this.socket.poll_recv(cx, &mut datagram))?;
+      this.len = datagram.filled().len();
+      this.pos = 0;
+    }
+
+    let n = (this.len - this.pos).min(buf.remaining());
+    buf.put_slice(&this.buffer[this.pos..this.pos + n]);
+    this.pos += n;
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl<D> AsyncWrite for DatagramAdapter<D>
+where
+  D: AsyncDatagramSocket + Unpin,
+{
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    let len = buf.len().min(MAX_DATAGRAM_SIZE);
+    self.socket.poll_send(cx, &buf[..len])
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl<D> WebSocket<DatagramAdapter<D>>
+where
+  D: AsyncDatagramSocket + Unpin,
+{
+  /// Creates a new `WebSocket` over a connected datagram socket.
+  ///
+  /// This is an experimental, non-standard transport; see [`DatagramAdapter`] for its limitations.
+  /// No handshake is performed and vectored writes are disabled so that every frame is sent in a
+  /// single datagram, as long as it fits in one.
+  pub fn over_datagram(socket: D, role: Role) -> Self {
+    let mut ws = Self::after_handshake(DatagramAdapter::new(socket), role);
+    ws.set_writev(false);
+    ws
+  }
+}
@@ -1,6 +1,7 @@
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum WebSocketError {
   #[error("Invalid fragment")]
   InvalidFragment,
@@ -8,6 +9,15 @@ pub enum WebSocketError {
   InvalidUTF8,
   #[error("Invalid continuation frame")]
   InvalidContinuationFrame,
+  #[error("Unexpected fragmented message")]
+  UnexpectedContinuation,
+  #[error("No matching frame found")]
+  NotFound,
+  #[error("Expected a {expected:?} frame, got {got:?}")]
+  UnexpectedOpcode {
+    expected: crate::OpCode,
+    got: crate::OpCode,
+  },
   #[error("Invalid status code: {0}")]
   InvalidStatusCode(u16),
   #[error("Invalid upgrade header")]
@@ -16,6 +26,10 @@ pub enum WebSocketError {
   InvalidConnectionHeader,
   #[error("Connection is closed")]
   ConnectionClosed,
+  #[error("A fragmented message is being written")]
+  MessageInProgress,
+  #[error("Closing handshake has not completed")]
+  CloseNotCompleted,
   #[error("Invalid close frame")]
   InvalidCloseFrame,
   #[error("Invalid close code")]
@@ -28,14 +42,32 @@ pub enum WebSocketError {
   ControlFrameFragmented,
   #[error("Ping frame too large")]
   PingFrameTooLarge,
+  #[error("Pong frame too large")]
+  PongFrameTooLarge,
+  #[error("Close reason too large")]
+  CloseReasonTooLarge,
   #[error("Frame too large")]
   FrameTooLarge,
+  #[error("Message too large")]
+  MessageTooLarge,
   #[error("Sec-Websocket-Version must be 13")]
   InvalidSecWebsocketVersion,
   #[error("Invalid value")]
   InvalidValue,
+  #[error("Reserved opcode: {0:#x}")]
+  ReservedOpcode(u8),
   #[error("Sec-WebSocket-Key header is missing")]
   MissingSecWebSocketKey,
+  #[error("Handshake timed out")]
+  HandshakeTimeout,
+  #[error("Pong not received in time")]
+  PongTimeout,
+  #[error("Write timed out")]
+  WriteTimeout,
+  #[error("No data received within the idle timeout")]
+  IdleTimeout,
+  #[error("Server accepted an extension that was not offered: {0}")]
+  UnexpectedExtension(String),
   #[error(transparent)]
   IoError(#[from] std::io::Error),
   #[cfg(feature = "upgrade")]
@@ -0,0 +1,51 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::CloseCode;
+use crate::Frame;
+use crate::OpCode;
+use crate::WebSocketError;
+
+/// A structured view of what was read from a WebSocket connection.
+///
+/// Returned by [`WebSocket::read_event`](crate::WebSocket::read_event).
+pub enum WebSocketEvent<'f> {
+  /// A text, binary or continuation frame.
+  Message(Frame<'f>),
+  /// A ping frame. Only reported when `auto_pong` is disabled.
+  Ping(Vec<u8>),
+  /// A pong frame.
+  Pong(Vec<u8>),
+  /// A close frame with its status code and reason. A close frame without a payload is
+  /// reported as [`CloseCode::Status`] with an empty reason.
+  Close(CloseCode, String),
+  /// A ping frame that was answered automatically because `auto_pong` is enabled.
+  Heartbeat,
+}
+
+impl<'f> WebSocketEvent<'f> {
+  pub(crate) fn from_frame(frame: Frame<'f>) -> Result<Self, WebSocketError> {
+    match frame.opcode {
+      OpCode::Ping => Ok(WebSocketEvent::Ping(frame.payload.into())),
+      OpCode::Pong => Ok(WebSocketEvent::Pong(frame.payload.into())),
+      OpCode::Close => {
+        let (code, reason) = crate::close::parse_close_payload(&frame.payload)?;
+        Ok(WebSocketEvent::Close(code, reason.to_owned()))
+      }
+      OpCode::Text | OpCode::Binary | OpCode::Continuation => {
+        Ok(WebSocketEvent::Message(frame))
+      }
+    }
+  }
+}
@@ -0,0 +1,142 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use crate::Frame;
+use crate::OpCode;
+use crate::Payload;
+use crate::WebSocketError;
+
+/// A hook that processes frames between the wire and the application.
+///
+/// On the read side, the hook is called after the payload has been unmasked and before the
+/// frame is validated and returned. On the write side, it is called before the frame is masked
+/// and serialized. Returning an error aborts the read or write.
+///
+/// Implemented for all `FnMut(&mut Frame) -> Result<(), WebSocketError>` closures.
+pub trait FrameExtension: Send {
+  fn process(&mut self, frame: &mut Frame) -> Result<(), WebSocketError>;
+
+  /// The reserved bits this extension uses, in their position in the first header byte: `0x40`
+  /// for RSV1, `0x20` for RSV2 and `0x10` for RSV3. Received frames with any other reserved bit set
+  /// fail with [`WebSocketError::ReservedBitsNotZero`].
+  ///
+  /// Default: `0`, no reserved bits. Use [`WithRsv`] to claim bits for a closure.
+  fn rsv_mask(&self) -> u8 {
+    0
+  }
+}
+
+impl<F> FrameExtension for F
+where
+  F: FnMut(&mut Frame) -> Result<(), WebSocketError> + Send,
+{
+  fn process(&mut self, frame: &mut Frame) -> Result<(), WebSocketError> {
+    self(frame)
+  }
+}
+
+/// Wraps a [`FrameExtension`] to declare the reserved bits it uses, see
+/// [`FrameExtension::rsv_mask`].
+pub struct WithRsv<E> {
+  rsv_mask: u8,
+  extension: E,
+}
+
+impl<E> WithRsv<E> {
+  pub fn new(rsv_mask: u8, extension: E) -> Self {
+    Self {
+      rsv_mask,
+      extension,
+    }
+  }
+}
+
+impl<E: FrameExtension> FrameExtension for WithRsv<E> {
+  fn process(&mut self, frame: &mut Frame) -> Result<(), WebSocketError> {
+    self.extension.process(frame)
+  }
+
+  fn rsv_mask(&self) -> u8 {
+    self.rsv_mask
+  }
+}
+
+/// User-defined transforms applied to the payload of data frames, e.g. encryption, custom
+/// compression or protocol tagging.
+///
+/// `on_receive` is called for every received text, binary and continuation frame once it has been
+/// unmasked, and `on_send` for every outgoing one before it is masked. For fragmented messages, the
+/// hooks are called once per fragment. Control frames are passed through unchanged.
+///
+/// Install it with [`WebSocket::set_extension`](crate::WebSocket::set_extension).
+pub trait ExtensionRegistry: Send {
+  fn on_receive(
+    &mut self,
+    opcode: OpCode,
+    payload: &mut Vec<u8>,
+  ) -> Result<(), WebSocketError>;
+
+  fn on_send(
+    &mut self,
+    opcode: OpCode,
+    payload: &mut Vec<u8>,
+  ) -> Result<(), WebSocketError>;
+}
+
+type SharedRegistry = Arc<Mutex<Box<dyn ExtensionRegistry>>>;
+
+/// Adapts one side of an `ExtensionRegistry` to a `FrameExtension`. The registry is shared so that
+/// both halves of a connection can use it.
+pub(crate) struct RegistryHook {
+  registry: SharedRegistry,
+  send: bool,
+}
+
+impl RegistryHook {
+  pub(crate) fn pair(registry: Box<dyn ExtensionRegistry>) -> (Self, Self) {
+    let registry = Arc::new(Mutex::new(registry));
+    (
+      Self {
+        registry: registry.clone(),
+        send: false,
+      },
+      Self {
+        registry,
+        send: true,
+      },
+    )
+  }
+}
+
+impl FrameExtension for RegistryHook {
+  fn process(&mut self, frame: &mut Frame) -> Result<(), WebSocketError> {
+    if frame.opcode.is_control() {
+      return Ok(());
+    }
+
+    let mut payload: Vec<u8> =
+      std::mem::replace(&mut frame.payload, Payload::Owned(Vec::new())).into();
+    let mut registry = self.registry.lock().unwrap();
+    if self.send {
+      registry.on_send(frame.opcode, &mut payload)?;
+    } else {
+      registry.on_receive(frame.opcode, &mut payload)?;
+    }
+    frame.payload = Payload::Owned(payload);
+    Ok(())
+  }
+}
@@ -19,6 +19,7 @@ use crate::error::WebSocketError;
 use crate::frame::Frame;
 use crate::OpCode;
 use crate::ReadHalf;
+use crate::Utf8Validation;
 use crate::WebSocket;
 #[cfg(feature = "unstable-split")]
 use crate::WebSocketRead;
@@ -26,6 +27,22 @@ use crate::WriteHalf;
 use tokio::io::AsyncRead;
 use tokio::io::AsyncWrite;
 
+/// Counters about the messages reassembled by a [`FragmentCollector`], see
+/// [`FragmentCollector::enable_stats`].
+///
+/// An unfragmented message counts as a single fragment. Control frames are not counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FragmentStats {
+  /// Fragments received so far for the message that is being reassembled.
+  pub fragments_in_current_message: usize,
+  /// Messages returned since the stats were enabled.
+  pub total_messages_assembled: u64,
+  /// Payload bytes of the messages returned since the stats were enabled.
+  pub total_bytes_assembled: u64,
+  /// The largest number of fragments a returned message was made of.
+  pub max_fragments_seen: usize,
+}
+
 pub enum Fragment {
   Text(Option<utf8::Incomplete>, Vec<u8>),
   Binary(Vec<u8>),
@@ -90,38 +107,75 @@ impl<'f, S> FragmentCollector<S> {
     let (stream, read_half, write_half) = ws.into_parts_internal();
     FragmentCollector {
       stream,
+      fragments: Fragments::new(read_half.utf8_validation),
       read_half,
       write_half,
-      fragments: Fragments::new(),
     }
   }
 
+  /// Sets the maximum total size in bytes of a message reassembled from fragments. If the fragments
+  /// received so far exceed it, reading fails with [`WebSocketError::MessageTooLarge`].
+  ///
+  /// Default: 64 MiB
+  pub fn set_max_accumulated_size(&mut self, limit: usize) {
+    self.fragments.max_accumulated_size = limit;
+  }
+
+  /// Starts counting fragments and reassembled messages, see [`FragmentStats`]. Counting is off by
+  /// default so that it costs nothing unless enabled; enabling it again resets the counters.
+  pub fn enable_stats(&mut self) {
+    self.fragments.stats = Some(Box::default());
+  }
+
+  /// Returns the counters collected since `enable_stats` was called, or all zeros if it was not.
+  pub fn fragment_stats(&self) -> FragmentStats {
+    self.fragments.stats.as_deref().copied().unwrap_or_default()
+  }
+
+  /// Sets which received payloads are checked to be valid UTF-8, including reassembled text
+  /// messages. See [`Utf8Validation`].
+  ///
+  /// Default: the mode of the wrapped connection
+  pub fn set_utf8_validation(&mut self, mode: Utf8Validation) {
+    self.read_half.utf8_validation = mode;
+    self.fragments.validate_utf8 = mode == Utf8Validation::Strict;
+  }
+
   /// Reads a WebSocket frame, collecting fragmented messages until the final frame is received and returns the completed message.
   ///
-  /// Text frames payload is guaranteed to be valid UTF-8.
+  /// Text frames payload is guaranteed to be valid UTF-8, unless validation is relaxed with
+  /// `set_utf8_validation`.
   pub async fn read_frame(&mut self) -> Result<Frame<'f>, WebSocketError>
   where
     S: AsyncRead + AsyncWrite + Unpin,
   {
-    loop {
-      let (res, obligated_send) =
-        self.read_half.read_frame_inner(&mut self.stream).await;
-      let is_closed = self.write_half.closed;
-      if let Some(obligated_send) = obligated_send {
-        if !is_closed {
-          self.write_frame(obligated_send).await?;
+    let read = async {
+      loop {
+        let (res, obligated_send) =
+          self.read_half.read_frame_inner(&mut self.stream).await;
+        let is_closed = self.write_half.closed;
+        if let Some(obligated_send) = obligated_send {
+          if !is_closed {
+            self.write_frame(obligated_send).await?;
+          }
+        }
+        let Some(frame) = res? else {
+          continue;
+        };
+        if is_closed && frame.opcode != OpCode::Close {
+          return Err(WebSocketError::ConnectionClosed);
+        }
+        if let Some(frame) = self.fragments.accumulate(frame)? {
+          return Ok(frame);
         }
       }
-      let Some(frame) = res? else {
-        continue;
-      };
-      if is_closed && frame.opcode != OpCode::Close {
-        return Err(WebSocketError::ConnectionClosed);
-      }
-      if let Some(frame) = self.fragments.accumulate(frame)? {
-        return Ok(frame);
-      }
-    }
+    };
+    #[cfg(feature = "tracing")]
+    let read = tracing::Instrument::instrument(
+      read,
+      tracing::debug_span!("fragment_collector_read_frame"),
+    );
+    read.await
   }
 
   /// See `WebSocket::write_frame`.
@@ -160,14 +214,43 @@ impl<'f, S> FragmentCollectorRead<S> {
     let (stream, read_half) = ws.into_parts_internal();
     FragmentCollectorRead {
       stream,
+      fragments: Fragments::new(read_half.utf8_validation),
       read_half,
-      fragments: Fragments::new(),
     }
   }
 
+  /// Sets the maximum total size in bytes of a message reassembled from fragments. If the fragments
+  /// received so far exceed it, reading fails with [`WebSocketError::MessageTooLarge`].
+  ///
+  /// Default: 64 MiB
+  pub fn set_max_accumulated_size(&mut self, limit: usize) {
+    self.fragments.max_accumulated_size = limit;
+  }
+
+  /// Starts counting fragments and reassembled messages, see [`FragmentStats`]. Counting is off by
+  /// default so that it costs nothing unless enabled; enabling it again resets the counters.
+  pub fn enable_stats(&mut self) {
+    self.fragments.stats = Some(Box::default());
+  }
+
+  /// Returns the counters collected since `enable_stats` was called, or all zeros if it was not.
+  pub fn fragment_stats(&self) -> FragmentStats {
+    self.fragments.stats.as_deref().copied().unwrap_or_default()
+  }
+
+  /// Sets which received payloads are checked to be valid UTF-8, including reassembled text
+  /// messages. See [`Utf8Validation`].
+  ///
+  /// Default: the mode of the wrapped connection
+  pub fn set_utf8_validation(&mut self, mode: Utf8Validation) {
+    self.read_half.utf8_validation = mode;
+    self.fragments.validate_utf8 = mode == Utf8Validation::Strict;
+  }
+
   /// Reads a WebSocket frame, collecting fragmented messages until the final frame is received and returns the completed message.
   ///
-  /// Text frames payload is guaranteed to be valid UTF-8.
+  /// Text frames payload is guaranteed to be valid UTF-8, unless validation is relaxed with
+  /// `set_utf8_validation`.
   pub async fn read_frame<R, E>(
     &mut self,
     send_fn: &mut impl FnMut(Frame<'f>) -> R,
@@ -198,13 +281,19 @@ impl<'f, S> FragmentCollectorRead<S> {
 struct Fragments {
   fragments: Option<Fragment>,
   opcode: OpCode,
+  max_accumulated_size: usize,
+  validate_utf8: bool,
+  stats: Option<Box<FragmentStats>>,
 }
 
 impl Fragments {
-  pub fn new() -> Self {
+  pub fn new(utf8_validation: Utf8Validation) -> Self {
     Self {
       fragments: None,
       opcode: OpCode::Close,
+      max_accumulated_size: 64 << 20,
+      validate_utf8: utf8_validation == Utf8Validation::Strict,
+      stats: None,
     }
   }
 
@@ -212,6 +301,38 @@ impl Fragments {
     &mut self,
     frame: Frame<'f>,
   ) -> Result<Option<Frame<'f>>, WebSocketError> {
+    let is_data = !frame.opcode.is_control();
+    let res = self.accumulate_frame(frame)?;
+    if let (true, Some(stats)) = (is_data, self.stats.as_deref_mut()) {
+      stats.fragments_in_current_message += 1;
+      if let Some(frame) = &res {
+        stats.max_fragments_seen = stats
+          .max_fragments_seen
+          .max(stats.fragments_in_current_message);
+        stats.fragments_in_current_message = 0;
+        stats.total_messages_assembled += 1;
+        stats.total_bytes_assembled += frame.payload.len() as u64;
+      }
+    }
+    Ok(res)
+  }
+
+  fn accumulate_frame<'f>(
+    &mut self,
+    frame: Frame<'f>,
+  ) -> Result<Option<Frame<'f>>, WebSocketError> {
+    if !frame.fin || frame.opcode == OpCode::Continuation {
+      let accumulated = match &self.fragments {
+        Some(Fragment::Text(_, buffer)) | Some(Fragment::Binary(buffer)) => {
+          buffer.len()
+        }
+        None => 0,
+      };
+      if accumulated + frame.payload.len() > self.max_accumulated_size {
+        return Err(WebSocketError::MessageTooLarge);
+      }
+    }
+
     match frame.opcode {
       OpCode::Text | OpCode::Binary => {
         if frame.fin {
@@ -221,6 +342,9 @@ impl Fragments {
           return Ok(Some(Frame::new(true, frame.opcode, None, frame.payload)));
         } else {
           self.fragments = match frame.opcode {
+            OpCode::Text if !self.validate_utf8 => {
+              Some(Fragment::Text(None, frame.payload.into()))
+            }
             OpCode::Text => match utf8::decode(&frame.payload) {
               Ok(text) => Some(Fragment::Text(None, text.as_bytes().to_vec())),
               Err(utf8::DecodeError::Incomplete {
@@ -244,6 +368,17 @@ impl Fragments {
         None => {
           return Err(WebSocketError::InvalidContinuationFrame);
         }
+        Some(Fragment::Text(_, input)) if !self.validate_utf8 => {
+          input.extend_from_slice(&frame.payload);
+          if frame.fin {
+            return Ok(Some(Frame::new(
+              true,
+              self.opcode,
+              None,
+              self.fragments.take().unwrap().take_buffer().into(),
+            )));
+          }
+        }
         Some(Fragment::Text(data, input)) => {
           let mut tail = &frame.payload[..];
           if let Some(mut incomplete) = data.take() {
@@ -309,3 +444,54 @@ impl Fragments {
     Ok(None)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_utils::server_with_frames;
+
+  #[tokio::test]
+  async fn fragment_collector_max_accumulated_size() {
+    let (server, _client) = server_with_frames([
+      Frame::new(false, OpCode::Binary, None, vec![0; 6].into()),
+      Frame::new(false, OpCode::Continuation, None, vec![0; 6].into()),
+    ])
+    .await;
+    let mut server = FragmentCollector::new(server);
+    server.set_max_accumulated_size(10);
+
+    assert!(matches!(
+      server.read_frame().await,
+      Err(WebSocketError::MessageTooLarge)
+    ));
+  }
+
+  #[tokio::test]
+  async fn fragment_stats() {
+    let (server, _client) = server_with_frames([
+      Frame::new(false, OpCode::Binary, None, b"ab"[..].into()),
+      Frame::new(false, OpCode::Continuation, None, b"cd"[..].into()),
+      Frame::new(true, OpCode::Continuation, None, b"e"[..].into()),
+      Frame::text(b"fgh"[..].into()),
+      Frame::new(false, OpCode::Text, None, b"ij"[..].into()),
+      Frame::pong(b""[..].into()),
+    ])
+    .await;
+    let mut server = FragmentCollector::new(server);
+    assert_eq!(server.fragment_stats(), FragmentStats::default());
+    server.enable_stats();
+
+    assert_eq!(server.read_frame().await.unwrap().payload, b"abcde");
+    assert_eq!(server.read_frame().await.unwrap().payload, b"fgh");
+    assert_eq!(server.read_frame().await.unwrap().opcode, OpCode::Pong);
+    assert_eq!(
+      server.fragment_stats(),
+      FragmentStats {
+        fragments_in_current_message: 1,
+        total_messages_assembled: 2,
+        total_bytes_assembled: 8,
+        max_fragments_seen: 3,
+      }
+    );
+  }
+}
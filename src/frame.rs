@@ -14,9 +14,11 @@
 
 use tokio::io::AsyncWriteExt;
 
+use bytes::Bytes;
 use bytes::BytesMut;
 use core::ops::Deref;
 
+use crate::close::CloseFrame;
 use crate::WebSocketError;
 
 macro_rules! repr_u8 {
@@ -96,7 +98,7 @@ impl From<Payload<'_>> for Vec<u8> {
   }
 }
 
-impl Payload<'_> {
+impl<'a> Payload<'a> {
   #[inline(always)]
   pub fn to_mut(&mut self) -> &mut [u8] {
     match self {
@@ -112,6 +114,74 @@ impl Payload<'_> {
       Payload::Bytes(b) => b.as_mut(),
     }
   }
+
+  /// Converts borrowed payloads into an owned buffer so that they can grow.
+  fn make_growable(&mut self) {
+    match self {
+      Payload::Borrowed(borrowed) => *self = Payload::Owned(borrowed.to_vec()),
+      Payload::BorrowedMut(borrowed) => {
+        *self = Payload::Owned(borrowed.to_vec())
+      }
+      Payload::Owned(_) | Payload::Bytes(_) => {}
+    }
+  }
+
+  /// Appends `chunk` to the payload. Borrowed payloads are copied into an owned buffer first.
+  pub fn extend_from_slice(&mut self, chunk: &[u8]) {
+    self.make_growable();
+    match self {
+      Payload::Owned(owned) => owned.extend_from_slice(chunk),
+      Payload::Bytes(b) => b.extend_from_slice(chunk),
+      _ => unreachable!(),
+    }
+  }
+
+  /// Reserves capacity for at least `additional` more bytes. Borrowed payloads are copied
+  /// into an owned buffer first.
+  pub fn reserve(&mut self, additional: usize) {
+    self.make_growable();
+    match self {
+      Payload::Owned(owned) => owned.reserve(additional),
+      Payload::Bytes(b) => b.reserve(additional),
+      _ => unreachable!(),
+    }
+  }
+
+  /// Shortens the payload to `len` bytes. Has no effect if `len` is greater than the current length.
+  pub fn truncate(&mut self, len: usize) {
+    match self {
+      Payload::Borrowed(borrowed) => {
+        *borrowed = &borrowed[..len.min(borrowed.len())]
+      }
+      Payload::BorrowedMut(borrowed) => {
+        let len = len.min(borrowed.len());
+        *borrowed = &mut std::mem::take(borrowed)[..len];
+      }
+      Payload::Owned(owned) => owned.truncate(len),
+      Payload::Bytes(b) => b.truncate(len),
+    }
+  }
+
+  /// Splits the payload at `at`, keeping `[at, len)` and returning `[0, at)`.
+  fn split_to(&mut self, at: usize) -> Payload<'a> {
+    match self {
+      Payload::Borrowed(borrowed) => {
+        let (head, tail) = borrowed.split_at(at);
+        *borrowed = tail;
+        Payload::Borrowed(head)
+      }
+      Payload::BorrowedMut(borrowed) => {
+        let (head, tail) = std::mem::take(borrowed).split_at_mut(at);
+        *borrowed = tail;
+        Payload::BorrowedMut(head)
+      }
+      Payload::Owned(owned) => {
+        let tail = owned.split_off(at);
+        Payload::Owned(std::mem::replace(owned, tail))
+      }
+      Payload::Bytes(b) => Payload::Bytes(b.split_to(at)),
+    }
+  }
 }
 
 impl<'a> PartialEq<&'_ [u8]> for Payload<'a> {
@@ -126,6 +196,32 @@ impl<'a, const N: usize> PartialEq<&'_ [u8; N]> for Payload<'a> {
   }
 }
 
+/// The header fields of a frame whose payload was read into a caller-supplied buffer, see
+/// [`WebSocket::read_frame_into`](crate::WebSocket::read_frame_into).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+  /// Indicates if this is the final frame in a message.
+  pub fin: bool,
+  /// The opcode of the frame.
+  pub opcode: OpCode,
+  /// The length of the payload, after extensions have processed it.
+  pub payload_len: usize,
+}
+
+/// Timing and size information about a received frame, see
+/// [`WebSocket::read_frame_with_metadata`](crate::WebSocket::read_frame_with_metadata).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameMetadata {
+  /// When the frame was completely read.
+  pub received_at: std::time::Instant,
+  /// The size of the frame on the wire, header included.
+  pub wire_bytes: usize,
+  /// The size of the payload.
+  pub payload_bytes: usize,
+  /// Whether the payload was masked by the peer.
+  pub was_masked: bool,
+}
+
 /// Represents a WebSocket frame.
 pub struct Frame<'f> {
   /// Indicates if this is the final frame in a message.
@@ -133,12 +229,14 @@ pub struct Frame<'f> {
   /// The opcode of the frame.
   pub opcode: OpCode,
   /// The masking key of the frame, if any.
-  mask: Option<[u8; 4]>,
+  pub(crate) mask: Option<[u8; 4]>,
+  /// The RSV1, RSV2 and RSV3 bits, in their position in the first header byte.
+  pub(crate) rsv: u8,
   /// The payload of the frame.
   pub payload: Payload<'f>,
 }
 
-const MAX_HEAD_SIZE: usize = 16;
+pub(crate) const MAX_HEAD_SIZE: usize = 16;
 
 impl<'f> Frame<'f> {
   /// Creates a new WebSocket `Frame`.
@@ -152,6 +250,7 @@ impl<'f> Frame<'f> {
       fin,
       opcode,
       mask,
+      rsv: 0,
       payload,
     }
   }
@@ -166,6 +265,7 @@ impl<'f> Frame<'f> {
       fin: true,
       opcode: OpCode::Text,
       mask: None,
+      rsv: 0,
       payload,
     }
   }
@@ -178,6 +278,7 @@ impl<'f> Frame<'f> {
       fin: true,
       opcode: OpCode::Binary,
       mask: None,
+      rsv: 0,
       payload,
     }
   }
@@ -196,6 +297,7 @@ impl<'f> Frame<'f> {
       fin: true,
       opcode: OpCode::Close,
       mask: None,
+      rsv: 0,
       payload: payload.into(),
     }
   }
@@ -210,6 +312,7 @@ impl<'f> Frame<'f> {
       fin: true,
       opcode: OpCode::Close,
       mask: None,
+      rsv: 0,
       payload,
     }
   }
@@ -222,10 +325,74 @@ impl<'f> Frame<'f> {
       fin: true,
       opcode: OpCode::Pong,
       mask: None,
+      rsv: 0,
       payload,
     }
   }
 
+  fn set_rsv(&mut self, bit: u8, value: bool) {
+    if value {
+      self.rsv |= bit;
+    } else {
+      self.rsv &= !bit;
+    }
+  }
+
+  /// Sets the RSV1 bit, e.g. for an extension that marks compressed messages with it. Reserved
+  /// bits are only accepted by peers that negotiated an extension using them.
+  pub fn set_rsv1(&mut self, value: bool) {
+    self.set_rsv(0b01000000, value);
+  }
+
+  /// Sets the RSV2 bit. See [`Frame::set_rsv1`].
+  pub fn set_rsv2(&mut self, value: bool) {
+    self.set_rsv(0b00100000, value);
+  }
+
+  /// Sets the RSV3 bit. See [`Frame::set_rsv1`].
+  pub fn set_rsv3(&mut self, value: bool) {
+    self.set_rsv(0b00010000, value);
+  }
+
+  /// Returns the RSV1 bit.
+  pub fn rsv1(&self) -> bool {
+    self.rsv & 0b01000000 != 0
+  }
+
+  /// Returns the RSV2 bit.
+  pub fn rsv2(&self) -> bool {
+    self.rsv & 0b00100000 != 0
+  }
+
+  /// Returns the RSV3 bit.
+  pub fn rsv3(&self) -> bool {
+    self.rsv & 0b00010000 != 0
+  }
+
+  pub(crate) fn with_rsv(mut self, rsv: u8) -> Self {
+    self.rsv = rsv;
+    self
+  }
+
+  /// Parses the payload of a close frame into a [`CloseFrame`], e.g. when `auto_close` is disabled
+  /// and close frames are handled by the application.
+  ///
+  /// See [`parse_close_payload`](crate::close::parse_close_payload) for how payloads of each length
+  /// are handled. Fails with [`WebSocketError::UnexpectedOpcode`] if this is not a close frame.
+  pub fn into_close_frame(self) -> Result<CloseFrame, WebSocketError> {
+    if self.opcode != OpCode::Close {
+      return Err(WebSocketError::UnexpectedOpcode {
+        expected: OpCode::Close,
+        got: self.opcode,
+      });
+    }
+    let (code, reason) = crate::close::parse_close_payload(&self.payload)?;
+    Ok(CloseFrame {
+      code,
+      reason: reason.to_owned(),
+    })
+  }
+
   /// Checks if the frame payload is valid UTF-8.
   pub fn is_utf8(&self) -> bool {
     #[cfg(feature = "simd")]
@@ -235,6 +402,69 @@ impl<'f> Frame<'f> {
     return std::str::from_utf8(&self.payload).is_ok();
   }
 
+  /// Returns the payload as a string slice if it is valid UTF-8.
+  ///
+  /// The opcode is not checked, so this also works for protocols that send text in binary frames.
+  pub fn payload_as_str(&self) -> Option<&str> {
+    #[cfg(feature = "simd")]
+    return simdutf8::basic::from_utf8(&self.payload).ok();
+
+    #[cfg(not(feature = "simd"))]
+    return std::str::from_utf8(&self.payload).ok();
+  }
+
+  /// Returns the payload as a byte slice.
+  pub fn payload_as_slice(&self) -> &[u8] {
+    &self.payload
+  }
+
+  /// Converts the payload into a `String`, failing with [`WebSocketError::InvalidUTF8`] if it is not
+  /// valid UTF-8. Owned payloads are not copied.
+  pub fn to_text(self) -> Result<String, WebSocketError> {
+    String::from_utf8(self.payload.into())
+      .map_err(|_| WebSocketError::InvalidUTF8)
+  }
+
+  /// Appends `chunk` to the frame payload.
+  ///
+  /// Together with [`Frame::reserve_payload`] and [`Frame::truncate_payload`], this allows a payload
+  /// to be built incrementally. The frame must not be masked yet.
+  pub fn extend_payload(&mut self, chunk: &[u8]) {
+    self.payload.extend_from_slice(chunk);
+  }
+
+  /// Reserves capacity for at least `additional` more payload bytes.
+  pub fn reserve_payload(&mut self, additional: usize) {
+    self.payload.reserve(additional);
+  }
+
+  /// Shortens the frame payload to `len` bytes. Has no effect if `len` is greater than the current length.
+  pub fn truncate_payload(&mut self, len: usize) {
+    self.payload.truncate(len);
+  }
+
+  /// Splits the frame into two fragments at byte `offset` of the payload.
+  ///
+  /// The first frame keeps the opcode and is not final. The second is a continuation frame that is
+  /// final if the original frame was. Payloads are split without copying where possible. The frames
+  /// are not masked, so split frames before they are masked for sending.
+  ///
+  /// Fails with [`WebSocketError::InvalidValue`] if `offset` is greater than the payload length or
+  /// the frame is a control frame, which must not be fragmented.
+  pub fn split_at_payload(
+    mut self,
+    offset: usize,
+  ) -> Result<(Frame<'f>, Frame<'f>), WebSocketError> {
+    if offset > self.payload.len() || self.opcode.is_control() {
+      return Err(WebSocketError::InvalidValue);
+    }
+    let head = self.payload.split_to(offset);
+    Ok((
+      Frame::new(false, self.opcode, None, head).with_rsv(self.rsv),
+      Frame::new(self.fin, OpCode::Continuation, None, self.payload),
+    ))
+  }
+
   pub fn mask(&mut self) {
     if let Some(mask) = self.mask {
       crate::mask::unmask(self.payload.to_mut(), mask);
@@ -259,8 +489,8 @@ impl<'f> Frame<'f> {
   /// # Panics
   ///
   /// This method panics if the head buffer is not at least n-bytes long, where n is the size of the length field (0, 2, 4, or 10)
-  pub fn fmt_head(&mut self, head: &mut [u8]) -> usize {
-    head[0] = (self.fin as u8) << 7 | (self.opcode as u8);
+  pub fn fmt_head(&self, head: &mut [u8]) -> usize {
+    head[0] = (self.fin as u8) << 7 | self.rsv | (self.opcode as u8);
 
     let len = self.payload.len();
     let size = if len < 126 {
@@ -334,11 +564,30 @@ impl<'f> Frame<'f> {
     buf[size..size + len].copy_from_slice(&self.payload);
     &buf[..size + len]
   }
+
+  /// Serializes the frame into a newly allocated buffer.
+  ///
+  /// The payload is written as is: if the frame has a mask, it is expected to be applied already,
+  /// e.g. with [`Frame::mask`].
+  pub fn to_vec(&self) -> Vec<u8> {
+    let len = self.payload.len();
+    let mut buf = vec![0; len + MAX_HEAD_SIZE];
+    let size = self.fmt_head(&mut buf);
+    buf[size..size + len].copy_from_slice(&self.payload);
+    buf.truncate(size + len);
+    buf
+  }
+
+  /// Serializes the frame into a newly allocated [`Bytes`]. See [`Frame::to_vec`].
+  pub fn to_bytes(&self) -> Bytes {
+    self.to_vec().into()
+  }
 }
 
 repr_u8! {
     #[repr(u8)]
     #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    #[non_exhaustive]
     pub enum OpCode {
         Continuation = 0x0,
         Text = 0x1,
@@ -349,7 +598,145 @@ repr_u8! {
     }
 }
 
-#[inline]
-pub fn is_control(opcode: OpCode) -> bool {
-  matches!(opcode, OpCode::Close | OpCode::Ping | OpCode::Pong)
+impl OpCode {
+  /// Returns `true` for control opcodes: `Close`, `Ping` and `Pong`.
+  #[inline]
+  pub fn is_control(self) -> bool {
+    matches!(self, OpCode::Close | OpCode::Ping | OpCode::Pong)
+  }
+
+  /// Returns `true` for opcodes that start a data message: `Text` and `Binary`.
+  #[inline]
+  pub fn is_data(self) -> bool {
+    matches!(self, OpCode::Text | OpCode::Binary)
+  }
+
+  /// Returns `true` for `Continuation`.
+  #[inline]
+  pub fn is_continuation(self) -> bool {
+    self == OpCode::Continuation
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn payload_as_text() {
+    let frame = Frame::text(b"hello"[..].into());
+    assert_eq!(frame.payload_as_str(), Some("hello"));
+    assert_eq!(frame.payload_as_slice(), b"hello");
+    assert_eq!(frame.to_text().unwrap(), "hello");
+
+    let frame = Frame::binary(vec![0xff].into());
+    assert_eq!(frame.payload_as_str(), None);
+    assert!(matches!(frame.to_text(), Err(WebSocketError::InvalidUTF8)));
+  }
+
+  #[test]
+  fn split_at_payload() {
+    let mut buf = *b"hello";
+    let payloads: [Payload; 4] = [
+      Payload::Borrowed(b"hello"),
+      Payload::BorrowedMut(&mut buf),
+      Payload::Owned(b"hello".to_vec()),
+      Payload::Bytes(BytesMut::from(&b"hello"[..])),
+    ];
+    for payload in payloads {
+      let (first, second) = Frame::text(payload).split_at_payload(2).unwrap();
+      assert!(!first.fin);
+      assert_eq!(first.opcode, OpCode::Text);
+      assert_eq!(first.payload, b"he");
+      assert!(second.fin);
+      assert_eq!(second.opcode, OpCode::Continuation);
+      assert_eq!(second.payload, b"llo");
+    }
+
+    let frame = Frame::new(false, OpCode::Binary, None, b"ab"[..].into());
+    let (first, second) = frame.split_at_payload(2).unwrap();
+    assert_eq!(first.payload, b"ab");
+    assert!(second.payload.is_empty());
+    assert!(!second.fin);
+
+    assert!(matches!(
+      Frame::binary(b"ab"[..].into()).split_at_payload(3),
+      Err(WebSocketError::InvalidValue)
+    ));
+    assert!(matches!(
+      Frame::pong(b"ab"[..].into()).split_at_payload(1),
+      Err(WebSocketError::InvalidValue)
+    ));
+  }
+
+  #[test]
+  fn incremental_payload() {
+    let mut frame = Frame::binary(b"hello"[..].into());
+    frame.reserve_payload(16);
+    frame.extend_payload(b", world");
+    assert_eq!(frame.payload, b"hello, world");
+
+    frame.truncate_payload(5);
+    assert_eq!(frame.payload, b"hello");
+    frame.truncate_payload(100);
+    assert_eq!(frame.payload, b"hello");
+
+    let mut buf = *b"abcdef";
+    let mut frame = Frame::binary(Payload::BorrowedMut(&mut buf));
+    frame.truncate_payload(3);
+    assert_eq!(frame.payload, b"abc");
+  }
+
+  #[test]
+  fn to_vec_matches_write() {
+    for len in [0, 125, 126, 65535, 65536] {
+      let mut frame = Frame::binary(vec![7; len].into());
+      let mut buf = Vec::new();
+      let written = frame.write(&mut buf).to_vec();
+      assert_eq!(frame.to_vec(), written);
+      assert_eq!(&frame.to_bytes()[..], &written[..]);
+    }
+  }
+
+  #[test]
+  fn fmt_head_rsv_bits() {
+    let mut frame = Frame::text(b"a"[..].into());
+    frame.set_rsv1(true);
+    frame.set_rsv3(true);
+    assert!(frame.rsv1() && !frame.rsv2() && frame.rsv3());
+    assert_eq!(frame.to_vec(), [0b1101_0001, 1, b'a']);
+
+    frame.set_rsv1(false);
+    assert_eq!(frame.to_vec()[0], 0b1001_0001);
+  }
+
+  #[test]
+  fn into_close_frame() {
+    use crate::CloseCode;
+
+    let frame = Frame::close_raw(b""[..].into()).into_close_frame().unwrap();
+    assert_eq!(frame.code, CloseCode::Status);
+    assert_eq!(frame.reason, "");
+
+    let frame = Frame::close(1000, b"").into_close_frame().unwrap();
+    assert_eq!(frame.code, CloseCode::Normal);
+    assert_eq!(frame.reason, "");
+
+    let frame = Frame::close(1011, b"oops").into_close_frame().unwrap();
+    assert_eq!(frame.code, CloseCode::Error);
+    assert_eq!(frame.reason, "oops");
+
+    assert!(matches!(
+      Frame::close(1000, b"\xff").into_close_frame(),
+      Err(WebSocketError::InvalidUTF8)
+    ));
+    assert!(matches!(
+      Frame::close_raw(b"\x03"[..].into()).into_close_frame(),
+      Err(WebSocketError::InvalidCloseFrame)
+    ));
+    assert!(matches!(
+      Frame::text(b""[..].into()).into_close_frame(),
+      Err(WebSocketError::UnexpectedOpcode { .. })
+    ));
+  }
 }
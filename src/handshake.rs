@@ -28,6 +28,7 @@ use tokio::io::AsyncWrite;
 use std::future::Future;
 use std::pin::Pin;
 
+use crate::upgrade::UpgradeFut;
 use crate::Role;
 use crate::WebSocket;
 use crate::WebSocketError;
@@ -103,15 +104,378 @@ where
   });
   executor.execute(fut);
 
+  let offered = request
+    .headers()
+    .get_all("Sec-WebSocket-Extensions")
+    .iter()
+    .filter_map(|h| h.to_str().ok())
+    .flat_map(parse_extension_header)
+    .collect::<Vec<_>>();
+
   let mut response = sender.send_request(request).await?;
-  verify(&response)?;
+  verify(&response, &offered)?;
+
+  let upgraded = hyper::upgrade::on(&mut response).await?;
+  let mut ws = WebSocket::after_handshake(TokioIo::new(upgraded), Role::Client);
+  let subprotocol = response
+    .headers()
+    .get("Sec-WebSocket-Protocol")
+    .and_then(|h| h.to_str().ok());
+  ws.set_subprotocol(subprotocol.map(str::to_owned));
+  Ok((ws, response))
+}
+
+/// Completes the server handshake on a raw stream whose upgrade request has already been parsed, e.g.
+/// by a low-level HTTP framework.
+///
+/// The `Sec-WebSocket-Key` and `Sec-WebSocket-Version` headers of `request` are checked like in
+/// [`upgrade::upgrade`](crate::upgrade::upgrade), then the `101 Switching Protocols` response is
+/// written to `stream` and a server-side `WebSocket` over it is returned. Nothing is read from
+/// `stream`; bytes the framework read past the request must be handed back with
+/// [`WebSocket::prepend_read_buffer`].
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::handshake;
+/// use fastwebsockets::WebSocket;
+/// use tokio::net::TcpStream;
+/// use anyhow::Result;
+///
+/// async fn accept(request: hyper::Request<()>, stream: TcpStream) -> Result<WebSocket<TcpStream>> {
+///   Ok(handshake::from_parts(request, stream).await?)
+/// }
+/// ```
+pub async fn from_parts<S>(
+  request: Request<()>,
+  mut stream: S,
+) -> Result<WebSocket<S>, WebSocketError>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  use tokio::io::AsyncWriteExt;
+
+  let accept = crate::upgrade::accept_key(&request)?;
+  let response = format!(
+    "HTTP/1.1 101 Switching Protocols\r\n\
+     Upgrade: websocket\r\n\
+     Connection: Upgrade\r\n\
+     Sec-WebSocket-Accept: {accept}\r\n\
+     \r\n"
+  );
+  stream.write_all(response.as_bytes()).await?;
+  stream.flush().await?;
+  Ok(WebSocket::after_handshake(stream, Role::Server))
+}
+
+/// Wraps a pending hyper upgrade in a future that resolves to a server-side `WebSocket`.
+///
+/// Use this when the `101 Switching Protocols` response is built by hand instead of with
+/// [`upgrade::upgrade`](crate::upgrade::upgrade). The future only completes once the response has been
+/// sent; errors from hyper while taking over the connection are returned as
+/// [`WebSocketError::HTTPError`].
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::handshake;
+/// use http_body_util::Empty;
+/// use hyper::{body::{Bytes, Incoming}, Request, Response, StatusCode};
+///
+/// fn accept(mut req: Request<Incoming>, accept_key: &str) -> Response<Empty<Bytes>> {
+///   let fut = handshake::from_upgrade(hyper::upgrade::on(&mut req));
+///   tokio::spawn(async move {
+///     if let Ok(mut ws) = fut.await {
+///       // Serve the connection
+///     }
+///   });
+///
+///   Response::builder()
+///     .status(StatusCode::SWITCHING_PROTOCOLS)
+///     .header("Connection", "upgrade")
+///     .header("Upgrade", "websocket")
+///     .header("Sec-WebSocket-Accept", accept_key)
+///     .body(Empty::new())
+///     .unwrap()
+/// }
+/// ```
+pub fn from_upgrade(upgrade: hyper::upgrade::OnUpgrade) -> UpgradeFut {
+  UpgradeFut::new(upgrade)
+}
+
+/// Connects to `target` through an HTTP proxy that supports the `CONNECT` method and performs the
+/// client handshake over the tunnel.
+///
+/// `target` is a `ws://` URL; the port defaults to 80. If `auth` is given, it is sent as
+/// `Proxy-Authorization: Basic` credentials. The proxy must answer the `CONNECT` request with
+/// `200`, otherwise [`WebSocketError::InvalidStatusCode`] is returned with its status. The upgrade
+/// response is verified like in [`client`].
+///
+/// The tunnel is a plain TCP connection, so `wss://` targets are not supported; use [`client`] with a
+/// TLS stream established over a tunnel instead.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::handshake;
+/// use std::net::SocketAddr;
+/// use anyhow::Result;
+///
+/// async fn connect(proxy: SocketAddr) -> Result<()> {
+///   let mut ws = handshake::connect_via_proxy(
+///     proxy,
+///     "ws://example.com/chat",
+///     Some(("user", "secret")),
+///   )
+///   .await?;
+///   let frame = ws.read_frame().await?;
+///   Ok(())
+/// }
+/// ```
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+pub async fn connect_via_proxy(
+  proxy_addr: std::net::SocketAddr,
+  target: &str,
+  auth: Option<(&str, &str)>,
+) -> Result<WebSocket<tokio::net::TcpStream>, WebSocketError> {
+  use tokio::io::AsyncWriteExt;
+
+  let (authority, path) = parse_ws_target(target)?;
+  let mut stream = tokio::net::TcpStream::connect(proxy_addr).await?;
+
+  let mut connect =
+    format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+  if let Some((user, password)) = auth {
+    let credentials = STANDARD.encode(format!("{user}:{password}"));
+    connect.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+  }
+  connect.push_str("\r\n");
+  stream.write_all(connect.as_bytes()).await?;
+
+  let mut buf = Vec::new();
+  let (response, _) = read_response_head(&mut stream, &mut buf).await?;
+  if response.status() != StatusCode::OK {
+    return Err(WebSocketError::InvalidStatusCode(
+      response.status().as_u16(),
+    ));
+  }
+  tcp_handshake(stream, &authority, &path).await
+}
+
+/// Splits a `ws://` URL into the `host:port` to connect to and the request path.
+#[cfg(feature = "tcp")]
+fn parse_ws_target(target: &str) -> Result<(String, String), WebSocketError> {
+  let uri: hyper::Uri =
+    target.parse().map_err(|_| WebSocketError::InvalidValue)?;
+  if uri.scheme_str() != Some("ws") {
+    return Err(WebSocketError::InvalidValue);
+  }
+  let host = uri.host().ok_or(WebSocketError::InvalidValue)?;
+  let authority = format!("{}:{}", host, uri.port_u16().unwrap_or(80));
+  let path = uri.path_and_query().map_or("/", |p| p.as_str());
+  Ok((authority, path.to_owned()))
+}
+
+/// Performs the client handshake for `path` on `authority` directly over a TCP connection.
+#[cfg(feature = "tcp")]
+async fn tcp_handshake(
+  mut stream: tokio::net::TcpStream,
+  authority: &str,
+  path: &str,
+) -> Result<WebSocket<tokio::net::TcpStream>, WebSocketError> {
+  use tokio::io::AsyncWriteExt;
+
+  let request = format!(
+    "GET {path} HTTP/1.1\r\n\
+     Host: {authority}\r\n\
+     Upgrade: websocket\r\n\
+     Connection: Upgrade\r\n\
+     Sec-WebSocket-Key: {}\r\n\
+     Sec-WebSocket-Version: 13\r\n\
+     \r\n",
+    generate_key(),
+  );
+  stream.write_all(request.as_bytes()).await?;
+
+  let mut buf = Vec::new();
+  let (response, head_len) = read_response_head(&mut stream, &mut buf).await?;
+  verify(&response, &[])?;
+
+  let mut ws = WebSocket::after_handshake(stream, Role::Client);
+  // Frames sent right after the response may have been read along with it.
+  if buf.len() > head_len {
+    ws.prepend_read_buffer(buf.split_off(head_len));
+  }
+  Ok(ws)
+}
+
+#[cfg(feature = "tcp")]
+type OnRetry = Box<dyn Fn(u32, std::time::Duration) + Send + Sync>;
+
+/// A client that connects to a `ws://` URL over TCP and retries failed attempts with exponential
+/// backoff, e.g. to reconnect after a network interruption.
+///
+/// Every attempt opens a new TCP connection and performs the handshake; an attempt fails if
+/// either step fails. The delay before the first retry is the initial backoff and is multiplied by
+/// the factor after every retry, up to the maximum backoff.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::handshake::RetryingWebSocketClient;
+/// use std::time::Duration;
+/// use anyhow::Result;
+///
+/// async fn connect() -> Result<()> {
+///   let mut client = RetryingWebSocketClient::new("ws://localhost:9001/chat");
+///   client.set_max_retries(10);
+///   client.set_backoff(Duration::from_millis(100), Duration::from_secs(30), 2.0);
+///   client.set_on_retry(|attempt, delay| {
+///     eprintln!("reconnecting, attempt {} in {:?}", attempt, delay);
+///   });
+///   let mut ws = client.connect().await?;
+///   let frame = ws.read_frame().await?;
+///   Ok(())
+/// }
+/// ```
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+pub struct RetryingWebSocketClient {
+  target: String,
+  max_retries: u32,
+  initial_backoff: std::time::Duration,
+  max_backoff: std::time::Duration,
+  factor: f64,
+  on_retry: Option<OnRetry>,
+}
+
+#[cfg(feature = "tcp")]
+impl RetryingWebSocketClient {
+  /// Creates a client for `target`, a `ws://` URL whose port defaults to 80.
+  pub fn new(target: impl Into<String>) -> Self {
+    Self {
+      target: target.into(),
+      max_retries: 5,
+      initial_backoff: std::time::Duration::from_millis(100),
+      max_backoff: std::time::Duration::from_secs(10),
+      factor: 2.0,
+      on_retry: None,
+    }
+  }
 
-  match hyper::upgrade::on(&mut response).await {
-    Ok(upgraded) => Ok((
-      WebSocket::after_handshake(TokioIo::new(upgraded), Role::Client),
-      response,
-    )),
-    Err(e) => Err(e.into()),
+  /// Sets how many times a failed attempt is retried before the last error is returned.
+  ///
+  /// Default: 5
+  pub fn set_max_retries(&mut self, n: u32) {
+    self.max_retries = n;
+  }
+
+  /// Sets the delay before the first retry, the largest delay between retries and the factor the
+  /// delay is multiplied by after every retry.
+  ///
+  /// Default: 100 ms, 10 s and 2
+  ///
+  /// # Panics
+  ///
+  /// Panics if `factor` is less than 1 or not finite.
+  pub fn set_backoff(
+    &mut self,
+    initial: std::time::Duration,
+    max: std::time::Duration,
+    factor: f64,
+  ) {
+    assert!(
+      factor.is_finite() && factor >= 1.0,
+      "backoff factor must be finite and at least 1"
+    );
+    self.initial_backoff = initial;
+    self.max_backoff = max;
+    self.factor = factor;
+  }
+
+  /// Sets a callback that is called before every retry with the number of the retry, starting at
+  /// 1, and the delay before it.
+  pub fn set_on_retry(
+    &mut self,
+    on_retry: impl Fn(u32, std::time::Duration) + Send + Sync + 'static,
+  ) {
+    self.on_retry = Some(Box::new(on_retry));
+  }
+
+  /// Connects to the target, retrying failed attempts. Returns the error of the last attempt once
+  /// the retries are exhausted. An invalid target fails right away with
+  /// [`WebSocketError::InvalidValue`].
+  pub async fn connect(
+    &mut self,
+  ) -> Result<WebSocket<tokio::net::TcpStream>, WebSocketError> {
+    let (authority, path) = parse_ws_target(&self.target)?;
+    let mut delay = self.initial_backoff;
+    let mut attempt = 0;
+    loop {
+      let res = match tokio::net::TcpStream::connect(&authority).await {
+        Ok(stream) => tcp_handshake(stream, &authority, &path).await,
+        Err(e) => Err(e.into()),
+      };
+      match res {
+        Err(_) if attempt < self.max_retries => {
+          attempt += 1;
+          if let Some(on_retry) = &self.on_retry {
+            on_retry(attempt, delay);
+          }
+          tokio::time::sleep(delay).await;
+          delay = std::time::Duration::try_from_secs_f64(
+            delay.as_secs_f64() * self.factor,
+          )
+          .unwrap_or(self.max_backoff)
+          .min(self.max_backoff);
+        }
+        res => return res,
+      }
+    }
+  }
+}
+
+/// Reads from `stream` into `buf` until it holds a complete HTTP response head. Returns the parsed
+/// head and its length; bytes after it are left in `buf`.
+#[cfg(feature = "tcp")]
+async fn read_response_head<S>(
+  stream: &mut S,
+  buf: &mut Vec<u8>,
+) -> Result<(Response<()>, usize), WebSocketError>
+where
+  S: AsyncRead + Unpin,
+{
+  use tokio::io::AsyncReadExt;
+
+  const MAX_HEAD_SIZE: usize = 16 * 1024;
+  let invalid = || {
+    std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      "invalid HTTP response",
+    )
+  };
+
+  loop {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Response::new(&mut headers);
+    if let httparse::Status::Complete(len) =
+      parsed.parse(buf).map_err(|_| invalid())?
+    {
+      let mut response = Response::builder().status(parsed.code.unwrap_or(0));
+      for header in parsed.headers.iter() {
+        response = response.header(header.name, header.value);
+      }
+      let response = response.body(()).map_err(|_| invalid())?;
+      return Ok((response, len));
+    }
+
+    if buf.len() >= MAX_HEAD_SIZE {
+      return Err(invalid().into());
+    }
+    if stream.read_buf(buf).await? == 0 {
+      return Err(WebSocketError::UnexpectedEOF);
+    }
   }
 }
 
@@ -123,8 +487,138 @@ pub fn generate_key() -> String {
   STANDARD.encode(r)
 }
 
+/// An extension offered or accepted in the `Sec-WebSocket-Extensions` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionOffer {
+  /// The extension name, e.g. `permessage-deflate`.
+  pub name: String,
+  /// The extension parameters in order. Parameters without a value have `None`.
+  pub params: Vec<(String, Option<String>)>,
+}
+
+/// Parse the value of a `Sec-WebSocket-Extensions` header.
+///
+/// Extensions are separated by `,` and parameters by `;`. Quoted parameter values are unquoted.
+/// Empty entries are ignored.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::handshake::parse_extension_header;
+///
+/// let offers = parse_extension_header(
+///   "permessage-deflate; client_max_window_bits, x-custom",
+/// );
+/// assert_eq!(offers[0].name, "permessage-deflate");
+/// assert_eq!(offers[0].params, [("client_max_window_bits".to_string(), None)]);
+/// assert_eq!(offers[1].name, "x-custom");
+/// ```
+pub fn parse_extension_header(value: &str) -> Vec<ExtensionOffer> {
+  split_unquoted(value, ',')
+    .into_iter()
+    .filter_map(|extension| {
+      let mut parts = split_unquoted(extension, ';').into_iter();
+      let name = parts.next()?.trim();
+      if name.is_empty() {
+        return None;
+      }
+
+      let params = parts
+        .filter_map(|param| {
+          let (key, value) = match param.split_once('=') {
+            Some((key, value)) => (key, Some(unquote(value.trim()))),
+            None => (param, None),
+          };
+          let key = key.trim();
+          (!key.is_empty()).then(|| (key.to_string(), value))
+        })
+        .collect();
+
+      Some(ExtensionOffer {
+        name: name.to_string(),
+        params,
+      })
+    })
+    .collect()
+}
+
+/// Format extensions as the value of a `Sec-WebSocket-Extensions` header.
+///
+/// Parameter values that are not valid tokens are quoted.
+pub fn format_extension_header(extensions: &[ExtensionOffer]) -> String {
+  let mut out = String::new();
+  for (i, extension) in extensions.iter().enumerate() {
+    if i > 0 {
+      out.push_str(", ");
+    }
+    out.push_str(&extension.name);
+    for (key, value) in &extension.params {
+      out.push_str("; ");
+      out.push_str(key);
+      if let Some(value) = value {
+        out.push('=');
+        if !value.is_empty() && value.bytes().all(is_token_char) {
+          out.push_str(value);
+        } else {
+          out.push('"');
+          out.push_str(&value.replace('\\', "\\\\").replace('"', "\\\""));
+          out.push('"');
+        }
+      }
+    }
+  }
+  out
+}
+
+/// Split `value` on `separator`, ignoring separators inside quoted strings.
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+  let mut parts = Vec::new();
+  let mut quoted = false;
+  let mut escaped = false;
+  let mut start = 0;
+  for (i, c) in value.char_indices() {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' if quoted => escaped = true,
+      '"' => quoted = !quoted,
+      c if c == separator && !quoted => {
+        parts.push(&value[start..i]);
+        start = i + 1;
+      }
+      _ => {}
+    }
+  }
+  parts.push(&value[start..]);
+  parts
+}
+
+fn unquote(value: &str) -> String {
+  match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+    Some(inner) => {
+      let mut out = String::with_capacity(inner.len());
+      let mut chars = inner.chars();
+      while let Some(c) = chars.next() {
+        match c {
+          '\\' => out.extend(chars.next()),
+          c => out.push(c),
+        }
+      }
+      out
+    }
+    None => value.to_string(),
+  }
+}
+
+// https://www.rfc-editor.org/rfc/rfc7230#section-3.2.6
+fn is_token_char(c: u8) -> bool {
+  c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
 // https://github.com/snapview/tungstenite-rs/blob/314feea3055a93e585882fb769854a912a7e6dae/src/handshake/client.rs#L189
-fn verify(response: &Response<Incoming>) -> Result<(), WebSocketError> {
+fn verify<B>(
+  response: &Response<B>,
+  offered: &[ExtensionOffer],
+) -> Result<(), WebSocketError> {
   if response.status() != StatusCode::SWITCHING_PROTOCOLS {
     return Err(WebSocketError::InvalidStatusCode(
       response.status().as_u16(),
@@ -151,5 +645,66 @@ fn verify(response: &Response<Incoming>) -> Result<(), WebSocketError> {
     return Err(WebSocketError::InvalidConnectionHeader);
   }
 
+  // The server must not accept an extension the client did not offer (RFC 6455 Section 4.1).
+  for accepted in headers
+    .get_all("Sec-WebSocket-Extensions")
+    .iter()
+    .filter_map(|h| h.to_str().ok())
+    .flat_map(parse_extension_header)
+  {
+    if !offered.iter().any(|offer| offer.name == accepted.name) {
+      return Err(WebSocketError::UnexpectedExtension(accepted.name));
+    }
+  }
+
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn offer(name: &str, params: &[(&str, Option<&str>)]) -> ExtensionOffer {
+    ExtensionOffer {
+      name: name.to_string(),
+      params: params
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.map(str::to_string)))
+        .collect(),
+    }
+  }
+
+  #[test]
+  fn parse_extensions() {
+    assert_eq!(
+      parse_extension_header(
+        "permessage-deflate; client_max_window_bits; server_max_window_bits=10, x-foo;bar=\"a,b;c\", ,"
+      ),
+      [
+        offer(
+          "permessage-deflate",
+          &[
+            ("client_max_window_bits", None),
+            ("server_max_window_bits", Some("10"))
+          ]
+        ),
+        offer("x-foo", &[("bar", Some("a,b;c"))]),
+      ]
+    );
+    assert!(parse_extension_header("").is_empty());
+  }
+
+  #[test]
+  fn format_extensions_roundtrip() {
+    let extensions = [
+      offer("permessage-deflate", &[("client_max_window_bits", None)]),
+      offer("x-foo", &[("bar", Some("a b")), ("baz", Some("1"))]),
+    ];
+    let header = format_extension_header(&extensions);
+    assert_eq!(
+      header,
+      "permessage-deflate; client_max_window_bits, x-foo; bar=\"a b\"; baz=1"
+    );
+    assert_eq!(parse_extension_header(&header), extensions);
+  }
+}
@@ -0,0 +1,152 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+
+use bytes::Buf;
+use bytes::Bytes;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+use crate::Role;
+use crate::WebSocket;
+
+fn into_io_error(e: h2::Error) -> std::io::Error {
+  if e.is_io() {
+    e.into_io().unwrap()
+  } else {
+    std::io::Error::new(std::io::ErrorKind::Other, e)
+  }
+}
+
+/// An HTTP/2 stream opened with an extended CONNECT request (RFC 8441), as a byte stream.
+///
+/// Reading yields the DATA frames received from the peer and writing sends DATA frames,
+/// respecting the stream's flow control window. Shutting down the writer ends the stream.
+pub struct H2Stream {
+  send: h2::SendStream<Bytes>,
+  recv: h2::RecvStream,
+  pending: Bytes,
+}
+
+impl H2Stream {
+  pub fn new(send: h2::SendStream<Bytes>, recv: h2::RecvStream) -> Self {
+    Self {
+      send,
+      recv,
+      pending: Bytes::new(),
+    }
+  }
+
+  /// Consumes the `H2Stream` and returns the underlying h2 streams.
+  pub fn into_inner(self) -> (h2::SendStream<Bytes>, h2::RecvStream) {
+    (self.send, self.recv)
+  }
+}
+
+impl AsyncRead for H2Stream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let this = self.get_mut();
+    while this.pending.is_empty() {
+      match ready!(this.recv.poll_data(cx)) {
+        Some(Ok(data)) => {
+          this
+            .recv
+            .flow_control()
+            .release_capacity(data.len())
+            .map_err(into_io_error)?;
+          this.pending = data;
+        }
+        Some(Err(e)) => return Poll::Ready(Err(into_io_error(e))),
+        None => return Poll::Ready(Ok(())),
+      }
+    }
+
+    let n = this.pending.len().min(buf.remaining());
+    buf.put_slice(&this.pending[..n]);
+    this.pending.advance(n);
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl AsyncWrite for H2Stream {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    let this = self.get_mut();
+    if buf.is_empty() {
+      return Poll::Ready(Ok(0));
+    }
+
+    this.send.reserve_capacity(buf.len());
+    let n = match ready!(this.send.poll_capacity(cx)) {
+      Some(Ok(n)) => n.min(buf.len()),
+      Some(Err(e)) => return Poll::Ready(Err(into_io_error(e))),
+      None => {
+        return Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+      }
+    };
+    this
+      .send
+      .send_data(Bytes::copy_from_slice(&buf[..n]), false)
+      .map_err(into_io_error)?;
+    Poll::Ready(Ok(n))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    // Data is handed to the h2 connection as soon as it is written.
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    self
+      .get_mut()
+      .send
+      .send_data(Bytes::new(), true)
+      .map_err(into_io_error)?;
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl WebSocket<H2Stream> {
+  /// Creates a new `WebSocket` over an HTTP/2 stream bootstrapped with an extended CONNECT
+  /// request, as specified in RFC 8441.
+  ///
+  /// The CONNECT request and response must already have been exchanged: `send` and `recv` are the
+  /// request body and response body streams on the client, or the other way around on the server.
+  /// Framing, including masking of client frames, is identical to HTTP/1.1.
+  pub fn after_h2_upgrade(
+    send: h2::SendStream<Bytes>,
+    recv: h2::RecvStream,
+    role: Role,
+  ) -> Self {
+    Self::after_handshake(H2Stream::new(send, recv), role)
+  }
+}
@@ -150,22 +150,52 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-mod close;
+mod broadcast;
+mod byte_stream;
+/// Close codes and close frame payloads.
+pub mod close;
+mod coalesce;
+#[cfg(feature = "experimental")]
+mod datagram;
 mod error;
+mod event;
+mod extension;
 mod fragment;
 mod frame;
 /// Client handshake.
 #[cfg(feature = "upgrade")]
 #[cfg_attr(docsrs, doc(cfg(feature = "upgrade")))]
 pub mod handshake;
+#[cfg(feature = "h2")]
+mod http2;
 mod mask;
+mod middleware;
+mod multiplex;
+mod pipe;
+mod rate_limit;
+#[cfg(feature = "router")]
+mod router;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "sink")]
+mod sink;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(any(test, feature = "test-utils"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-utils")))]
+pub mod test_utils;
+#[cfg(feature = "tls")]
+mod tls;
 /// HTTP upgrades.
 #[cfg(feature = "upgrade")]
 #[cfg_attr(docsrs, doc(cfg(feature = "upgrade")))]
 pub mod upgrade;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 use bytes::Buf;
 
+use bytes::Bytes;
 use bytes::BytesMut;
 #[cfg(feature = "unstable-split")]
 use std::future::Future;
@@ -175,15 +205,50 @@ use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 
+pub use crate::broadcast::BroadcastGroup;
+pub use crate::byte_stream::WebSocketByteStream;
 pub use crate::close::CloseCode;
+pub use crate::close::CloseFrame;
+pub use crate::coalesce::CoalescingWriter;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub use crate::datagram::AsyncDatagramSocket;
+#[cfg(feature = "experimental")]
+#[cfg_attr(docsrs, doc(cfg(feature = "experimental")))]
+pub use crate::datagram::DatagramAdapter;
 pub use crate::error::WebSocketError;
+pub use crate::event::WebSocketEvent;
+pub use crate::extension::ExtensionRegistry;
+pub use crate::extension::FrameExtension;
+pub use crate::extension::WithRsv;
 pub use crate::fragment::FragmentCollector;
 #[cfg(feature = "unstable-split")]
 pub use crate::fragment::FragmentCollectorRead;
+pub use crate::fragment::FragmentStats;
 pub use crate::frame::Frame;
+pub use crate::frame::FrameHeader;
+pub use crate::frame::FrameMetadata;
 pub use crate::frame::OpCode;
 pub use crate::frame::Payload;
+#[cfg(feature = "h2")]
+#[cfg_attr(docsrs, doc(cfg(feature = "h2")))]
+pub use crate::http2::H2Stream;
 pub use crate::mask::unmask;
+pub use crate::mask::MaskingState;
+pub use crate::middleware::FrameStream;
+pub use crate::middleware::Middleware;
+pub use crate::middleware::WebSocketMiddleware;
+pub use crate::multiplex::ChannelId;
+pub use crate::multiplex::MultiplexedWebSocket;
+pub use crate::pipe::pipe;
+#[cfg(feature = "router")]
+pub use crate::router::FrameRouter;
+#[cfg(feature = "sink")]
+pub use crate::sink::SharedStream;
+#[cfg(feature = "sink")]
+pub use crate::sink::WebSocketSink;
+#[cfg(feature = "wasm")]
+pub use crate::wasm::WasmStream;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Role {
@@ -191,6 +256,43 @@ pub enum Role {
   Client,
 }
 
+/// Which received payloads are checked to be valid UTF-8.
+///
+/// Invalid payloads fail with [`WebSocketError::InvalidUTF8`]. Skipping validation is only safe with
+/// trusted peers: text payloads are then not guaranteed to be valid UTF-8.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Utf8Validation {
+  /// Validate text frames, messages reassembled by [`FragmentCollector`] and close reasons.
+  #[default]
+  Strict,
+  /// Skip validation of text frames and messages. Close reasons are still validated.
+  Relaxed,
+  /// Skip all validation, including close reasons. Close reasons are only read as strings where
+  /// they are needed, e.g. by the close handler, which then sees an empty reason.
+  None,
+}
+
+/// A snapshot of the settings of a [`WebSocket`], taken with [`WebSocket::save_config`] and
+/// applied again with [`WebSocket::restore_config`], e.g. to revert a temporary change of
+/// `max_message_size` for one phase of a protocol.
+///
+/// Callbacks, extensions and rate limits are not part of the snapshot. More fields may be added in
+/// future versions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WebSocketConfig {
+  pub auto_close: bool,
+  pub auto_pong: bool,
+  pub auto_pong_drop: bool,
+  pub auto_apply_mask: bool,
+  pub vectored: bool,
+  pub writev_threshold: usize,
+  pub max_message_size: usize,
+  pub max_control_frame_size: usize,
+  pub read_buffer_capacity: usize,
+  pub utf8_validation: Utf8Validation,
+}
+
 pub(crate) struct WriteHalf {
   role: Role,
   closed: bool,
@@ -198,16 +300,60 @@ pub(crate) struct WriteHalf {
   auto_apply_mask: bool,
   writev_threshold: usize,
   write_buffer: Vec<u8>,
+  /// Bytes of `write_buffer` that still have to be written, left over from a write that was cancelled.
+  pending: std::ops::Range<usize>,
+  /// Serialized frames waiting to be written after `pending`: control frames that go ahead of the
+  /// next data frame, and frames queued by the sink and byte stream.
+  priority_write_buffer: Vec<u8>,
+  rate_limit: Option<rate_limit::RateLimiter>,
+  extension: Option<Box<dyn FrameExtension>>,
+  /// A message written with `write_chunk` is in progress.
+  streaming: bool,
+  /// A `FragmentedSender` was dropped before finishing its message, which can't be ended anymore.
+  poisoned: bool,
 }
 
+/// Progress of the frame currently being read. Kept in `ReadHalf` so that a cancelled read
+/// can be resumed without losing the already consumed header.
+#[derive(Clone, Copy)]
+enum ReadState {
+  /// Waiting for a complete frame header.
+  Header,
+  /// The header has been consumed, waiting for `payload_len` bytes of payload.
+  Payload {
+    fin: bool,
+    rsv: u8,
+    opcode: OpCode,
+    mask: Option<[u8; 4]>,
+    payload_len: usize,
+  },
+}
+
+type OnPing = Box<dyn FnMut(Vec<u8>) -> Option<Vec<u8>> + Send>;
+type OnClose = Box<dyn FnMut(CloseCode, &str) -> (CloseCode, String) + Send>;
+
 pub(crate) struct ReadHalf {
   role: Role,
   auto_apply_mask: bool,
   auto_close: bool,
   auto_pong: bool,
+  auto_pong_drop: bool,
   writev_threshold: usize,
   max_message_size: usize,
+  max_control_frame_size: usize,
   buffer: BytesMut,
+  buffer_capacity: usize,
+  state: ReadState,
+  close_received: bool,
+  utf8_validation: Utf8Validation,
+  rate_limit: Option<rate_limit::RateLimiter>,
+  extension: Option<Box<dyn FrameExtension>>,
+  on_ping: Option<OnPing>,
+  on_close: Option<OnClose>,
+  idle_timeout: Option<std::time::Duration>,
+  /// When the last data frame or ping was received, or the idle timeout was set. Left unset
+  /// without an idle timeout, since `Instant::now` panics on `wasm32-unknown-unknown`.
+  last_activity: Option<tokio::time::Instant>,
 }
 
 #[cfg(feature = "unstable-split")]
@@ -271,6 +417,43 @@ impl<'f, S> WebSocketRead<S> {
     self.read_half.auto_pong = auto_pong;
   }
 
+  /// Sets a callback that is called with the payload of every received ping frame when
+  /// `auto_pong` is enabled.
+  ///
+  /// Returning `Some(payload)` sends a pong frame with that payload and returning `None`
+  /// suppresses the pong. A payload longer than 125 bytes does not fit in a control frame: no pong
+  /// is sent and the read fails with [`WebSocketError::PongFrameTooLarge`].
+  ///
+  /// Default: pong frames echo the ping payload.
+  pub fn set_on_ping<F>(&mut self, on_ping: F)
+  where
+    F: FnMut(Vec<u8>) -> Option<Vec<u8>> + Send + 'static,
+  {
+    self.read_half.on_ping = Some(Box::new(on_ping));
+  }
+
+  /// Sets a callback that chooses the close code and reason sent back when `auto_close` answers a
+  /// close frame. It is called with the received code and reason; a close frame without a payload is
+  /// reported as [`CloseCode::Status`]. Returning [`CloseCode::Status`] sends a close frame without a
+  /// payload. A reason longer than 123 bytes does not fit in a control frame: no close frame is sent
+  /// and the read fails with [`WebSocketError::CloseReasonTooLarge`].
+  ///
+  /// Default: the received close frame is echoed.
+  pub fn set_close_handler<F>(&mut self, on_close: F)
+  where
+    F: FnMut(CloseCode, &str) -> (CloseCode, String) + Send + 'static,
+  {
+    self.read_half.on_close = Some(Box::new(on_close));
+  }
+
+  /// Sets the close code sent back when `auto_close` answers a close frame, without a reason. `None`
+  /// echoes the received close frame. This replaces any callback set with `set_close_handler`.
+  ///
+  /// Default: `None`
+  pub fn set_auto_close_code(&mut self, code: Option<CloseCode>) {
+    self.read_half.set_auto_close_code(code);
+  }
+
   /// Sets the maximum message size in bytes. If a message is received that is larger than this, the connection will be closed.
   ///
   /// Default: 64 MiB
@@ -278,6 +461,31 @@ impl<'f, S> WebSocketRead<S> {
     self.read_half.max_message_size = max_message_size;
   }
 
+  /// Sets the maximum payload size in bytes of control frames (close, ping and pong). Control
+  /// frames are not subject to `max_message_size`.
+  ///
+  /// Default: 125, the maximum allowed by RFC 6455
+  pub fn set_max_control_frame_size(&mut self, max_control_frame_size: usize) {
+    self.read_half.max_control_frame_size = max_control_frame_size;
+  }
+
+  /// Sets which received payloads are checked to be valid UTF-8. See [`Utf8Validation`].
+  ///
+  /// Default: [`Utf8Validation::Strict`]
+  pub fn set_utf8_validation(&mut self, mode: Utf8Validation) {
+    self.read_half.utf8_validation = mode;
+  }
+
+  /// Sets the capacity of the read buffer in bytes and allocates it up front.
+  ///
+  /// The buffer is grown back to this capacity before every read, so frames smaller than it are read
+  /// without new allocations once the previously returned payloads have been dropped.
+  ///
+  /// Default: 8 KiB
+  pub fn set_read_buffer_capacity(&mut self, capacity: usize) {
+    self.read_half.set_buffer_capacity(capacity);
+  }
+
   /// Sets whether to automatically apply the mask to the frame payload.
   ///
   /// Default: `true`
@@ -285,6 +493,17 @@ impl<'f, S> WebSocketRead<S> {
     self.read_half.auto_apply_mask = auto_apply_mask;
   }
 
+  /// Sets a hook that is called on every received frame after it has been
+  /// unmasked and before it is returned.
+  ///
+  /// See [`FrameExtension`].
+  pub fn set_read_extension(
+    &mut self,
+    extension: impl FrameExtension + 'static,
+  ) {
+    self.read_half.extension = Some(Box::new(extension));
+  }
+
   /// Reads a frame from the stream.
   pub async fn read_frame<R, E>(
     &mut self,
@@ -329,6 +548,17 @@ impl<'f, S> WebSocketWrite<S> {
     self.write_half.auto_apply_mask = auto_apply_mask;
   }
 
+  /// Sets a hook that is called on every outgoing frame before it is masked
+  /// and serialized.
+  ///
+  /// See [`FrameExtension`].
+  pub fn set_write_extension(
+    &mut self,
+    extension: impl FrameExtension + 'static,
+  ) {
+    self.write_half.extension = Some(Box::new(extension));
+  }
+
   pub fn is_closed(&self) -> bool {
     self.write_half.closed
   }
@@ -349,12 +579,15 @@ pub struct WebSocket<S> {
   stream: S,
   write_half: WriteHalf,
   read_half: ReadHalf,
+  subprotocol: Option<String>,
 }
 
 impl<'f, S> WebSocket<S> {
   /// Creates a new `WebSocket` from a stream that has already completed the WebSocket handshake.
   ///
-  /// Use the `upgrade` feature to handle server upgrades and client handshakes.
+  /// Use the `upgrade` feature to handle server upgrades and client handshakes. The HTTP upgrade is optional:
+  /// for in-process or IPC connections where both ends agree to speak WebSocket framing, the stream can be
+  /// used directly.
   ///
   /// # Example
   ///
@@ -379,6 +612,56 @@ impl<'f, S> WebSocket<S> {
       stream,
       write_half: WriteHalf::after_handshake(role),
       read_half: ReadHalf::after_handshake(role),
+      subprotocol: None,
+    }
+  }
+
+  /// Creates a new `WebSocket` like [`WebSocket::after_handshake`], using the given buffers instead of
+  /// allocating new ones, e.g. when buffers come from a per-connection pool.
+  ///
+  /// Both buffers are cleared and used with their current capacity. The read buffer is a [`BytesMut`] since
+  /// received payloads are split off it without copying. It is kept at its
+  /// capacity (see [`WebSocket::set_read_buffer_capacity`]); it is the caller's responsibility to make
+  /// it large enough for the expected frames, or it will grow like any other buffer.
+  pub fn after_handshake_with_buffers(
+    stream: S,
+    role: Role,
+    mut write_buf: Vec<u8>,
+    mut read_buf: BytesMut,
+  ) -> Self
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    write_buf.clear();
+    read_buf.clear();
+    Self {
+      stream,
+      write_half: WriteHalf::with_buffer(role, write_buf),
+      read_half: ReadHalf::with_buffer(role, read_buf),
+      subprotocol: None,
+    }
+  }
+
+  /// Creates a new `WebSocket` from a [`BufReader`](tokio::io::BufReader) over a stream that has already
+  /// completed the WebSocket handshake, e.g. after parsing the HTTP upgrade by hand.
+  ///
+  /// Bytes already buffered by the reader are moved into the WebSocket's read buffer and the reader
+  /// is unwrapped. `WebSocket` does its own read-ahead buffering, so keeping the `BufReader` would only
+  /// add a copy.
+  pub fn with_buf_reader(
+    reader: tokio::io::BufReader<S>,
+    role: Role,
+  ) -> WebSocket<S>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let mut read_half = ReadHalf::after_handshake(role);
+    read_half.buffer.extend_from_slice(reader.buffer());
+    Self {
+      stream: reader.into_inner(),
+      write_half: WriteHalf::after_handshake(role),
+      read_half,
+      subprotocol: None,
     }
   }
 
@@ -409,6 +692,29 @@ impl<'f, S> WebSocket<S> {
     )
   }
 
+  /// Split a [`WebSocket`] into a [`WebSocketRead`] and a [`WebSocketSink`] that share the underlying stream
+  /// through a [`SharedStream`] handle. Unlike [`WebSocket::split`], this does not require the stream to be splittable.
+  #[cfg(feature = "sink")]
+  pub fn split_sink(
+    self,
+  ) -> (
+    WebSocketRead<SharedStream<S>>,
+    WebSocketSink<SharedStream<S>>,
+  )
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let (stream, read, write) = self.into_parts_internal();
+    let stream = SharedStream::new(stream);
+    (
+      WebSocketRead {
+        stream: stream.clone(),
+        read_half: read,
+      },
+      WebSocketSink::new(stream, write),
+    )
+  }
+
   /// Consumes the `WebSocket` and returns the underlying stream.
   #[inline]
   pub fn into_inner(self) -> S {
@@ -416,6 +722,36 @@ impl<'f, S> WebSocket<S> {
     self.stream
   }
 
+  /// Consumes the `WebSocket` and returns it as a byte stream of message payloads, e.g. to run a
+  /// binary protocol that speaks `AsyncRead + AsyncWrite` over it. See [`WebSocketByteStream`].
+  pub fn into_async_read_write(self) -> WebSocketByteStream<S> {
+    WebSocketByteStream::new(self)
+  }
+
+  /// Consumes the `WebSocket` and wraps the underlying stream in a [`tokio_util::codec::Framed`], to
+  /// switch to a different framing protocol over the same connection.
+  ///
+  /// Bytes that were read ahead but not parsed as frames are moved to the read buffer of the
+  /// `Framed`, and the unwritten bytes of queued frames to its write buffer, so no data is lost. The
+  /// switch must happen at a frame boundary, i.e. not after a cancelled read.
+  #[cfg(feature = "codec")]
+  #[cfg_attr(docsrs, doc(cfg(feature = "codec")))]
+  pub fn into_framed<C>(self, codec: C) -> tokio_util::codec::Framed<S, C>
+  where
+    S: AsyncRead + AsyncWrite,
+  {
+    let (stream, read_half, write_half) = self.into_parts_internal();
+    let mut framed = tokio_util::codec::Framed::new(stream, codec);
+    framed
+      .read_buffer_mut()
+      .extend_from_slice(&read_half.buffer);
+    let write_buffer = framed.write_buffer_mut();
+    write_buffer
+      .extend_from_slice(&write_half.write_buffer[write_half.pending.clone()]);
+    write_buffer.extend_from_slice(&write_half.priority_write_buffer);
+    framed
+  }
+
   /// Consumes the `WebSocket` and returns the underlying stream.
   #[inline]
   pub(crate) fn into_parts_internal(self) -> (S, ReadHalf, WriteHalf) {
@@ -448,6 +784,53 @@ impl<'f, S> WebSocket<S> {
     self.read_half.auto_pong = auto_pong;
   }
 
+  /// Sets whether received pong frames are silently discarded, e.g. unsolicited pongs sent as
+  /// heartbeats. Unlike `auto_pong`, nothing is sent. This also applies to a [`FragmentCollector`],
+  /// the split read half and the byte stream built from this connection. `ping_rtt` still sees the
+  /// pongs it waits for.
+  ///
+  /// Default: `false`
+  pub fn set_auto_pong_drop(&mut self, auto_pong_drop: bool) {
+    self.read_half.auto_pong_drop = auto_pong_drop;
+  }
+
+  /// Sets a callback that is called with the payload of every received ping frame when
+  /// `auto_pong` is enabled.
+  ///
+  /// Returning `Some(payload)` sends a pong frame with that payload and returning `None`
+  /// suppresses the pong. A payload longer than 125 bytes does not fit in a control frame: no pong
+  /// is sent and the read fails with [`WebSocketError::PongFrameTooLarge`].
+  ///
+  /// Default: pong frames echo the ping payload.
+  pub fn set_on_ping<F>(&mut self, on_ping: F)
+  where
+    F: FnMut(Vec<u8>) -> Option<Vec<u8>> + Send + 'static,
+  {
+    self.read_half.on_ping = Some(Box::new(on_ping));
+  }
+
+  /// Sets a callback that chooses the close code and reason sent back when `auto_close` answers a
+  /// close frame. It is called with the received code and reason; a close frame without a payload is
+  /// reported as [`CloseCode::Status`]. Returning [`CloseCode::Status`] sends a close frame without a
+  /// payload. A reason longer than 123 bytes does not fit in a control frame: no close frame is sent
+  /// and the read fails with [`WebSocketError::CloseReasonTooLarge`].
+  ///
+  /// Default: the received close frame is echoed.
+  pub fn set_close_handler<F>(&mut self, on_close: F)
+  where
+    F: FnMut(CloseCode, &str) -> (CloseCode, String) + Send + 'static,
+  {
+    self.read_half.on_close = Some(Box::new(on_close));
+  }
+
+  /// Sets the close code sent back when `auto_close` answers a close frame, without a reason. `None`
+  /// echoes the received close frame. This replaces any callback set with `set_close_handler`.
+  ///
+  /// Default: `None`
+  pub fn set_auto_close_code(&mut self, code: Option<CloseCode>) {
+    self.read_half.set_auto_close_code(code);
+  }
+
   /// Sets the maximum message size in bytes. If a message is received that is larger than this, the connection will be closed.
   ///
   /// Default: 64 MiB
@@ -455,6 +838,168 @@ impl<'f, S> WebSocket<S> {
     self.read_half.max_message_size = max_message_size;
   }
 
+  /// Sets the maximum payload size in bytes of control frames (close, ping and pong). Control
+  /// frames are not subject to `max_message_size`.
+  ///
+  /// Default: 125, the maximum allowed by RFC 6455
+  pub fn set_max_control_frame_size(&mut self, max_control_frame_size: usize) {
+    self.read_half.max_control_frame_size = max_control_frame_size;
+  }
+
+  /// Sets which received payloads are checked to be valid UTF-8. See [`Utf8Validation`].
+  ///
+  /// Default: [`Utf8Validation::Strict`]
+  pub fn set_utf8_validation(&mut self, mode: Utf8Validation) {
+    self.read_half.utf8_validation = mode;
+  }
+
+  /// Limits reading to approximately `bytes_per_sec` bytes of payload per second. A value of `0` removes
+  /// the limit.
+  ///
+  /// After each frame, the next read is delayed until the frame's payload has drained at this rate.
+  /// This protects servers against peers that flood them with data.
+  ///
+  /// Default: `0`
+  pub fn set_read_rate_limit(&mut self, bytes_per_sec: u64) {
+    self.read_half.rate_limit = rate_limit::RateLimiter::new(bytes_per_sec);
+  }
+
+  /// Limits `write_frame` to approximately `bytes_per_sec` bytes of payload per second. A value of `0`
+  /// removes the limit. Control frames sent with `write_frame_with_priority`, including
+  /// automatic replies, are not delayed.
+  ///
+  /// Default: `0`
+  pub fn set_write_rate_limit(&mut self, bytes_per_sec: u64) {
+    self.write_half.rate_limit = rate_limit::RateLimiter::new(bytes_per_sec);
+  }
+
+  /// Sets the capacity of the read buffer in bytes and allocates it up front.
+  ///
+  /// The buffer is grown back to this capacity before every read, so frames smaller than it are read
+  /// without new allocations once the previously returned payloads have been dropped.
+  ///
+  /// Default: 8 KiB
+  pub fn set_read_buffer_capacity(&mut self, capacity: usize) {
+    self.read_half.set_buffer_capacity(capacity);
+  }
+
+  /// Takes the bytes that have been read from the stream but not parsed yet, leaving the read buffer
+  /// empty. Returns `None` if nothing is buffered.
+  ///
+  /// Between frames these are the first bytes of the next frames. After a frame header has been read,
+  /// e.g. when a read was cancelled, they belong to the pending payload and taking them corrupts the
+  /// connection.
+  pub fn take_read_buffer(&mut self) -> Option<Vec<u8>> {
+    let buffer = &mut self.read_half.buffer;
+    if buffer.is_empty() {
+      return None;
+    }
+    let data = buffer.to_vec();
+    buffer.clear();
+    Some(data)
+  }
+
+  /// Inserts `data` in front of the bytes in the read buffer, so that it is parsed before anything
+  /// else is read from the stream. Use it to hand back bytes that were read past the end of the HTTP
+  /// handshake.
+  pub fn prepend_read_buffer(&mut self, data: Vec<u8>) {
+    let buffer = &mut self.read_half.buffer;
+    if buffer.is_empty() {
+      buffer.extend_from_slice(&data);
+      return;
+    }
+    let mut prepended = BytesMut::with_capacity(data.len() + buffer.len());
+    prepended.extend_from_slice(&data);
+    prepended.extend_from_slice(buffer);
+    *buffer = prepended;
+  }
+
+  /// Returns the subprotocol negotiated in the handshake, from the `Sec-WebSocket-Protocol` header
+  /// of the response. Set by [`handshake::client`] on the client; servers choose it themselves and
+  /// record it with `set_subprotocol`.
+  pub fn subprotocol(&self) -> Option<&str> {
+    self.subprotocol.as_deref()
+  }
+
+  /// Records the negotiated subprotocol, e.g. on a server after answering the upgrade request
+  /// with a `Sec-WebSocket-Protocol` header. It is only informational and does not change how
+  /// frames are handled.
+  pub fn set_subprotocol(&mut self, protocol: Option<String>) {
+    self.subprotocol = protocol;
+  }
+
+  /// Returns the current settings, see [`WebSocketConfig`].
+  pub fn save_config(&self) -> WebSocketConfig {
+    WebSocketConfig {
+      auto_close: self.read_half.auto_close,
+      auto_pong: self.read_half.auto_pong,
+      auto_pong_drop: self.read_half.auto_pong_drop,
+      auto_apply_mask: self.write_half.auto_apply_mask,
+      vectored: self.write_half.vectored,
+      writev_threshold: self.write_half.writev_threshold,
+      max_message_size: self.read_half.max_message_size,
+      max_control_frame_size: self.read_half.max_control_frame_size,
+      read_buffer_capacity: self.read_half.buffer_capacity,
+      utf8_validation: self.read_half.utf8_validation,
+    }
+  }
+
+  /// Applies settings returned by `save_config`.
+  pub fn restore_config(&mut self, config: WebSocketConfig) {
+    self.set_auto_close(config.auto_close);
+    self.set_auto_pong(config.auto_pong);
+    self.set_auto_pong_drop(config.auto_pong_drop);
+    self.set_auto_apply_mask(config.auto_apply_mask);
+    self.set_writev(config.vectored);
+    self.set_writev_threshold(config.writev_threshold);
+    self.set_max_message_size(config.max_message_size);
+    self.set_max_control_frame_size(config.max_control_frame_size);
+    self.set_read_buffer_capacity(config.read_buffer_capacity);
+    self.set_utf8_validation(config.utf8_validation);
+  }
+
+  /// Closes the connection with code 1001 (going away) if no data frame or ping is received within
+  /// `duration`, e.g. to reclaim idle connections on a busy server. The timer starts when this is
+  /// called and restarts with every data frame or ping; pongs do not count as activity.
+  ///
+  /// When it expires, the read in progress sends the close frame and fails with
+  /// [`WebSocketError::IdleTimeout`].
+  ///
+  /// Default: no timeout
+  pub fn set_idle_timeout(&mut self, duration: std::time::Duration) {
+    self.read_half.idle_timeout = Some(duration);
+    self.read_half.last_activity = Some(tokio::time::Instant::now());
+  }
+
+  /// Resets the connection to its state right after the handshake once the closing handshake has
+  /// completed, so that a new session can run over the same stream. Settings are kept.
+  ///
+  /// This is only valid for tunneled or custom protocols in which the underlying connection
+  /// outlives a single WebSocket session; RFC 6455 requires closing the TCP connection after the
+  /// closing handshake. Bytes that were read past the close frame are discarded.
+  ///
+  /// Fails with [`WebSocketError::CloseNotCompleted`] unless a close frame has been both received
+  /// and completely written.
+  pub fn reuse(&mut self) -> Result<(), WebSocketError> {
+    let write_half = &mut self.write_half;
+    if !self.read_half.close_received
+      || !write_half.closed
+      || !write_half.pending.is_empty()
+      || !write_half.priority_write_buffer.is_empty()
+    {
+      return Err(WebSocketError::CloseNotCompleted);
+    }
+
+    self.read_half.close_received = false;
+    self.read_half.state = ReadState::Header;
+    self.read_half.buffer.clear();
+    write_half.closed = false;
+    write_half.streaming = false;
+    write_half.poisoned = false;
+    write_half.pending = 0..0;
+    Ok(())
+  }
+
   /// Sets whether to automatically apply the mask to the frame payload.
   ///
   /// Default: `true`
@@ -463,8 +1008,68 @@ impl<'f, S> WebSocket<S> {
     self.write_half.auto_apply_mask = auto_apply_mask;
   }
 
+  /// Sets a hook that is called on every received frame after it has been
+  /// unmasked and before it is returned.
+  ///
+  /// See [`FrameExtension`].
+  pub fn set_read_extension(
+    &mut self,
+    extension: impl FrameExtension + 'static,
+  ) {
+    self.read_half.extension = Some(Box::new(extension));
+  }
+
+  /// Sets a hook that is called on every outgoing frame before it is masked
+  /// and serialized.
+  ///
+  /// See [`FrameExtension`].
+  pub fn set_write_extension(
+    &mut self,
+    extension: impl FrameExtension + 'static,
+  ) {
+    self.write_half.extension = Some(Box::new(extension));
+  }
+
+  /// Installs per-message transforms for received and outgoing data frames.
+  ///
+  /// This replaces the hooks set with [`WebSocket::set_read_extension`] and
+  /// [`WebSocket::set_write_extension`]. See [`ExtensionRegistry`].
+  pub fn set_extension(&mut self, extension: Box<dyn ExtensionRegistry>) {
+    let (read, write) = extension::RegistryHook::pair(extension);
+    self.read_half.extension = Some(Box::new(read));
+    self.write_half.extension = Some(Box::new(write));
+  }
+
+  /// Returns `true` once the closing handshake is complete, i.e. a close frame has been both sent and
+  /// received. No more frames can be written or read.
   pub fn is_closed(&self) -> bool {
-    self.write_half.closed
+    self.write_half.closed && self.read_half.close_received
+  }
+
+  /// Returns `true` while the closing handshake is in progress, i.e. a close frame has been sent or
+  /// received but not both. With `auto_close` enabled, a received close frame is answered right away.
+  pub fn is_closing(&self) -> bool {
+    self.write_half.closed != self.read_half.close_received
+  }
+
+  /// Returns the maximum message size in bytes. See [`WebSocket::set_max_message_size`].
+  pub fn max_message_size(&self) -> usize {
+    self.read_half.max_message_size
+  }
+
+  /// Returns whether close frames are answered automatically. See [`WebSocket::set_auto_close`].
+  pub fn auto_close(&self) -> bool {
+    self.read_half.auto_close
+  }
+
+  /// Returns whether ping frames are answered automatically. See [`WebSocket::set_auto_pong`].
+  pub fn auto_pong(&self) -> bool {
+    self.read_half.auto_pong
+  }
+
+  /// Returns whether large frames are written with vectored writes. See [`WebSocket::set_writev`].
+  pub fn vectored(&self) -> bool {
+    self.write_half.vectored
   }
 
   /// Writes a frame to the stream.
@@ -491,97 +1096,988 @@ impl<'f, S> WebSocket<S> {
   where
     S: AsyncRead + AsyncWrite + Unpin,
   {
-    self.write_half.write_frame(&mut self.stream, frame).await?;
-    Ok(())
+    let write = self.write_half.write_frame(&mut self.stream, frame);
+    #[cfg(feature = "tracing")]
+    let write = tracing::Instrument::instrument(
+      write,
+      tracing::debug_span!("write_frame"),
+    );
+    write.await
   }
 
-  /// Reads a frame from the stream.
-  ///
-  /// This method will unmask the frame payload. For fragmented frames, use `FragmentCollector::read_frame`.
+  /// Writes one chunk of a message that is streamed as a series of fragments.
   ///
-  /// Text frames payload is guaranteed to be valid UTF-8.
+  /// The first chunk is sent with `opcode`, which must be [`OpCode::Text`] or [`OpCode::Binary`], and
+  /// the following ones as continuation frames; `opcode` is ignored for them. `is_last` ends the
+  /// message, so a message that fits in a single chunk is sent as a regular frame. Control frames can
+  /// be written between chunks with [`WebSocket::write_frame`]; writing a text or binary frame before
+  /// the message has ended fails with [`WebSocketError::MessageInProgress`].
   ///
   /// # Example
   ///
   /// ```
-  /// use fastwebsockets::{OpCode, WebSocket, Frame};
+  /// use fastwebsockets::{OpCode, WebSocket, WebSocketError};
   /// use tokio::net::TcpStream;
-  /// use anyhow::Result;
   ///
-  /// async fn echo(
-  ///   ws: &mut WebSocket<TcpStream>
-  /// ) -> Result<()> {
-  ///   let frame = ws.read_frame().await?;
-  ///   match frame.opcode {
-  ///     OpCode::Text | OpCode::Binary => {
-  ///       ws.write_frame(frame).await?;
-  ///     }
-  ///     _ => {}
+  /// async fn stream_file(ws: &mut WebSocket<TcpStream>, data: &[u8]) -> Result<(), WebSocketError> {
+  ///   let mut chunks = data.chunks(4096).peekable();
+  ///   while let Some(chunk) = chunks.next() {
+  ///     ws.write_chunk(OpCode::Binary, chunk, chunks.peek().is_none()).await?;
   ///   }
   ///   Ok(())
   /// }
   /// ```
-  pub async fn read_frame(&mut self) -> Result<Frame<'f>, WebSocketError>
+  pub async fn write_chunk(
+    &mut self,
+    opcode: OpCode,
+    chunk: &[u8],
+    is_last: bool,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let opcode = if self.write_half.streaming {
+      OpCode::Continuation
+    } else if opcode.is_data() {
+      opcode
+    } else {
+      return Err(WebSocketError::InvalidValue);
+    };
+    self
+      .write_half
+      .write_frame(
+        &mut self.stream,
+        Frame::new(is_last, opcode, None, Payload::Borrowed(chunk)),
+      )
+      .await?;
+    self.write_half.streaming = !is_last;
+    Ok(())
+  }
+
+  /// Sends everything read from `reader` as one binary message, split into fragments of up to
+  /// `frame_size` bytes, so the payload never has to be in memory at once.
+  ///
+  /// The message ends when `reader` reaches EOF; if the payload size is a multiple of `frame_size`,
+  /// the final fragment is empty. Fails with [`WebSocketError::InvalidValue`] if `frame_size` is 0.
+  /// On an error the message is left unfinished, so the connection should be closed.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{WebSocket, WebSocketError};
+  /// use tokio::fs::File;
+  /// use tokio::net::TcpStream;
+  ///
+  /// async fn send_file(ws: &mut WebSocket<TcpStream>) -> Result<(), WebSocketError> {
+  ///   let file = File::open("video.mp4").await?;
+  ///   ws.send_binary_streaming(file, 64 * 1024).await
+  /// }
+  /// ```
+  pub async fn send_binary_streaming<R>(
+    &mut self,
+    mut reader: R,
+    frame_size: usize,
+  ) -> Result<(), WebSocketError>
   where
     S: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
   {
+    if frame_size == 0 {
+      return Err(WebSocketError::InvalidValue);
+    }
+    let mut chunk = vec![0; frame_size];
     loop {
-      let (res, obligated_send) =
-        self.read_half.read_frame_inner(&mut self.stream).await;
-      let is_closed = self.write_half.closed;
-      if let Some(frame) = obligated_send {
-        if !is_closed {
-          self.write_half.write_frame(&mut self.stream, frame).await?;
+      let mut filled = 0;
+      while filled < frame_size {
+        match reader.read(&mut chunk[filled..]).await? {
+          0 => break,
+          n => filled += n,
         }
       }
-      if let Some(frame) = res? {
-        if is_closed && frame.opcode != OpCode::Close {
-          return Err(WebSocketError::ConnectionClosed);
-        }
-        break Ok(frame);
+      let is_last = filled < frame_size;
+      self
+        .write_chunk(OpCode::Binary, &chunk[..filled], is_last)
+        .await?;
+      if is_last {
+        return Ok(());
       }
     }
   }
-}
-
-const MAX_HEADER_SIZE: usize = 14;
-
-impl ReadHalf {
-  pub fn after_handshake(role: Role) -> Self {
-    let buffer = BytesMut::with_capacity(8192);
-
-    Self {
-      role,
-      auto_apply_mask: true,
-      auto_close: true,
-      auto_pong: true,
-      writev_threshold: 1024,
-      max_message_size: 64 << 20,
-      buffer,
-    }
-  }
 
-  /// Attempt to read a single frame from from the incoming stream, returning any send obligations if
-  /// `auto_close` or `auto_pong` are enabled. Callers to this function are obligated to send the
-  /// frame in the latter half of the tuple if one is specified, unless the write half of this socket
-  /// has been closed.
+  /// Receives the next message and writes its payload to `writer` fragment by fragment, so the
+  /// message never has to be in memory at once. Returns the opcode of the message, [`OpCode::Text`]
+  /// or [`OpCode::Binary`].
   ///
-  /// XXX: Do not expose this method to the public API.
-  pub(crate) async fn read_frame_inner<'f, S>(
+  /// Fails with [`WebSocketError::MessageTooLarge`] once the payload exceeds `limit` bytes, and with
+  /// [`WebSocketError::ConnectionClosed`] if a close frame arrives first. Pongs and unanswered pings
+  /// received in between are skipped. Unlike with [`FragmentCollector`], the UTF-8 of fragmented
+  /// text messages is not validated.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{WebSocket, WebSocketError};
+  /// use tokio::fs::File;
+  /// use tokio::net::TcpStream;
+  ///
+  /// async fn receive_upload(ws: &mut WebSocket<TcpStream>) -> Result<(), WebSocketError> {
+  ///   let file = File::create("upload.bin").await?;
+  ///   ws.receive_binary_streaming(file, 1 << 30).await?;
+  ///   Ok(())
+  /// }
+  /// ```
+  pub async fn receive_binary_streaming<W>(
     &mut self,
-    stream: &mut S,
-  ) -> (Result<Option<Frame<'f>>, WebSocketError>, Option<Frame<'f>>)
+    mut writer: W,
+    limit: u64,
+  ) -> Result<OpCode, WebSocketError>
   where
-    S: AsyncRead + Unpin,
+    S: AsyncRead + AsyncWrite + Unpin,
+    W: AsyncWrite + Unpin,
   {
-    let mut frame = match self.parse_frame_header(stream).await {
-      Ok(frame) => frame,
-      Err(e) => return (Err(e), None),
-    };
+    let mut opcode = None;
+    let mut received = 0u64;
+    loop {
+      let frame = self.read_frame().await?;
+      match (frame.opcode, opcode) {
+        (OpCode::Close, _) => return Err(WebSocketError::ConnectionClosed),
+        (OpCode::Ping | OpCode::Pong, _) => continue,
+        (OpCode::Text | OpCode::Binary, None) => opcode = Some(frame.opcode),
+        (OpCode::Continuation, Some(_)) => {}
+        (OpCode::Continuation, None) => {
+          return Err(WebSocketError::InvalidContinuationFrame)
+        }
+        _ => return Err(WebSocketError::InvalidFragment),
+      }
 
-    if self.role == Role::Server && self.auto_apply_mask {
-      frame.unmask()
-    };
+      received += frame.payload.len() as u64;
+      if received > limit {
+        return Err(WebSocketError::MessageTooLarge);
+      }
+      writer.write_all(&frame.payload).await?;
+      if frame.fin {
+        writer.flush().await?;
+        return Ok(opcode.unwrap());
+      }
+    }
+  }
+
+  /// Starts a fragmented message of type `opcode` and returns a handle to write its fragments.
+  ///
+  /// The handle borrows the connection, so no other frames can be written until the message is
+  /// finished. See [`FragmentedSender`].
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{OpCode, WebSocket, WebSocketError};
+  /// use tokio::net::TcpStream;
+  ///
+  /// async fn send_rows(ws: &mut WebSocket<TcpStream>, rows: &[&str]) -> Result<(), WebSocketError> {
+  ///   let mut message = ws.begin_fragmented_message(OpCode::Text);
+  ///   for row in rows {
+  ///     message.write_chunk(row.as_bytes()).await?;
+  ///   }
+  ///   message.finish().await
+  /// }
+  /// ```
+  pub fn begin_fragmented_message(
+    &mut self,
+    opcode: OpCode,
+  ) -> FragmentedSender<'_, S> {
+    FragmentedSender { ws: self, opcode }
+  }
+
+  /// Starts the closing handshake after everything written before it: the remaining bytes of a
+  /// cancelled write and queued control frames are written first, then the close frame, and the
+  /// stream is flushed. [`CloseCode::Status`] sends a close frame without a payload.
+  ///
+  /// The peer's close reply still has to be read, e.g. with `read_frame`.
+  pub async fn send_close_and_drain(
+    &mut self,
+    code: CloseCode,
+    reason: &str,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self.write_half.flush_pending(&mut self.stream).await?;
+    let payload = close::encode_close_payload(code, reason);
+    self
+      .write_half
+      .write_frame(&mut self.stream, Frame::close_raw(payload.into()))
+      .await?;
+    self.stream.flush().await?;
+    Ok(())
+  }
+
+  /// Writes a frame with a single vectored write of its header and payload, regardless of
+  /// `set_writev` and the writev threshold. Any opcode can be written this way; masking is applied
+  /// in place before the write.
+  ///
+  /// Unlike `write_frame`, the frame is not copied into the write buffer, so if this future is
+  /// dropped partway, the part of the frame that was not written yet is lost.
+  pub async fn write_frame_scatter(
+    &mut self,
+    frame: Frame<'f>,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self
+      .write_half
+      .write_frame_vectored(&mut self.stream, frame)
+      .await
+  }
+
+  /// Writes a frame whose payload is borrowed from `payload`, e.g. a slice of a memory-mapped file or
+  /// of a buffer shared with a deserializer, without copying it into the write buffer. The header is
+  /// serialized from `header` and written together with the payload as in
+  /// [`WebSocket::write_frame_scatter`], which has the same cancellation caveat.
+  ///
+  /// Client frames have to be masked, which needs a masked copy of the payload, so the payload is only
+  /// passed through untouched on the server and when no write extension is set. Fails with
+  /// [`WebSocketError::InvalidValue`] if `header.payload_len` is not the length of `payload`.
+  pub async fn write_frame_zero_copy(
+    &mut self,
+    header: FrameHeader,
+    payload: &Bytes,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    if header.payload_len != payload.len() {
+      return Err(WebSocketError::InvalidValue);
+    }
+    let frame = Frame::new(
+      header.fin,
+      header.opcode,
+      None,
+      Payload::Borrowed(&payload[..]),
+    );
+    self
+      .write_half
+      .write_frame_vectored(&mut self.stream, frame)
+      .await
+  }
+
+  /// Writes a batch of unfragmented frames, given as payload and opcode pairs, with as few vectored
+  /// writes as possible: the headers are serialized into one buffer and the headers and payloads are
+  /// written interleaved, up to `UIO_MAXIOV` buffers per write.
+  ///
+  /// Payloads are not copied on the server; client payloads are masked on a copy. As with
+  /// [`WebSocket::write_frame_scatter`], if this future is dropped partway, the rest of the batch is
+  /// lost.
+  ///
+  /// The whole batch is checked before anything is written: it fails with
+  /// [`WebSocketError::ConnectionClosed`] if a frame follows a close frame or the connection is
+  /// already closed, with [`WebSocketError::MessageInProgress`] for a data frame while a message
+  /// written with [`WebSocket::write_chunk`] is in progress, and with
+  /// [`WebSocketError::InvalidValue`] for a continuation frame.
+  pub async fn write_batch(
+    &mut self,
+    frames: &[(&[u8], OpCode)],
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self.write_half.write_batch(&mut self.stream, frames).await
+  }
+
+  /// Writes a frame to the stream, sending control frames (close, ping and pong) ahead of any data
+  /// frame written later.
+  ///
+  /// A frame cannot be interrupted on the wire, so if a previous write was cancelled partway, its
+  /// remaining bytes are written first. If this future is dropped in turn, the control frame stays
+  /// queued and is sent before the next data frame. Automatic pong and close replies use this path.
+  pub async fn write_frame_with_priority(
+    &mut self,
+    frame: Frame<'f>,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self
+      .write_half
+      .write_frame_with_priority(&mut self.stream, frame)
+      .await
+  }
+
+  /// Writes a frame to the stream, failing with [`WebSocketError::WriteTimeout`] if it is not written
+  /// within `timeout`, e.g. because the peer stopped reading.
+  ///
+  /// The frame is always written through the internal write buffer. If the timeout expires after part
+  /// of the frame has been written, the rest is kept and sent before the next frame, so the connection
+  /// can still be used or closed cleanly.
+  pub async fn write_frame_timeout(
+    &mut self,
+    frame: Frame<'f>,
+    timeout: std::time::Duration,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let write = self
+      .write_half
+      .write_frame_buffered(&mut self.stream, frame);
+    tokio::time::timeout(timeout, write)
+      .await
+      .map_err(|_| WebSocketError::WriteTimeout)?
+  }
+
+  /// Reads a frame from the stream.
+  ///
+  /// This method will unmask the frame payload. For fragmented frames, use `FragmentCollector::read_frame`.
+  ///
+  /// Text frames payload is guaranteed to be valid UTF-8.
+  ///
+  /// # Cancel safety
+  ///
+  /// Partially read frames are kept in the connection state, so dropping the future and calling
+  /// `read_frame` again resumes where it left off. An automatic pong or close reply that is being
+  /// written when the future is dropped may be lost.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{OpCode, WebSocket, Frame};
+  /// use tokio::net::TcpStream;
+  /// use anyhow::Result;
+  ///
+  /// async fn echo(
+  ///   ws: &mut WebSocket<TcpStream>
+  /// ) -> Result<()> {
+  ///   let frame = ws.read_frame().await?;
+  ///   match frame.opcode {
+  ///     OpCode::Text | OpCode::Binary => {
+  ///       ws.write_frame(frame).await?;
+  ///     }
+  ///     _ => {}
+  ///   }
+  ///   Ok(())
+  /// }
+  /// ```
+  pub async fn read_frame(&mut self) -> Result<Frame<'f>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let read = async {
+      loop {
+        if let Some(frame) = self.read_next(false).await? {
+          break Ok(frame);
+        }
+      }
+    };
+    #[cfg(feature = "tracing")]
+    let read =
+      tracing::Instrument::instrument(read, tracing::debug_span!("read_frame"));
+    read.await
+  }
+
+  /// Reads a frame like [`WebSocket::read_frame`] and returns it with its [`FrameMetadata`], e.g.
+  /// to measure per-frame latency and bandwidth.
+  pub async fn read_frame_with_metadata(
+    &mut self,
+  ) -> Result<(Frame<'f>, FrameMetadata), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let frame = self.read_frame().await?;
+    let received_at = std::time::Instant::now();
+    let header = frame.fmt_head(&mut [0; frame::MAX_HEAD_SIZE]);
+    let metadata = FrameMetadata {
+      received_at,
+      wire_bytes: header + frame.payload.len(),
+      payload_bytes: frame.payload.len(),
+      was_masked: frame.mask.is_some(),
+    };
+    Ok((frame, metadata))
+  }
+
+  /// Reads a frame like [`WebSocket::read_frame`] and writes its payload into `buf`, which is
+  /// cleared first. Returns the header of the frame.
+  ///
+  /// Reusing the same `buf` for every read keeps a single long-lived allocation for payloads, and
+  /// lets the connection's read buffer be reused as soon as the frame has been copied out of it.
+  pub async fn read_frame_into(
+    &mut self,
+    buf: &mut Vec<u8>,
+  ) -> Result<FrameHeader, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let frame = self.read_frame().await?;
+    buf.clear();
+    buf.extend_from_slice(&frame.payload);
+    Ok(FrameHeader {
+      fin: frame.fin,
+      opcode: frame.opcode,
+      payload_len: buf.len(),
+    })
+  }
+
+  /// Reads a frame like [`WebSocket::read_frame`], for protocols that never fragment messages.
+  ///
+  /// Fails with [`WebSocketError::UnexpectedContinuation`] if the frame is part of a fragmented
+  /// message, i.e. it is a continuation frame or a data frame that is not final.
+  pub async fn read_frame_non_fragmented(
+    &mut self,
+  ) -> Result<Frame<'f>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let frame = self.read_frame().await?;
+    if frame.opcode == OpCode::Continuation || !frame.fin {
+      return Err(WebSocketError::UnexpectedContinuation);
+    }
+    Ok(frame)
+  }
+
+  /// Sends a ping frame with `data` as its payload.
+  ///
+  /// RFC 6455 limits control frame payloads to 125 bytes; larger payloads fail with
+  /// [`WebSocketError::PingFrameTooLarge`] without writing anything.
+  pub async fn send_ping(&mut self, data: &[u8]) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    if data.len() > 125 {
+      return Err(WebSocketError::PingFrameTooLarge);
+    }
+    self
+      .write_frame(Frame::new(true, OpCode::Ping, None, data.into()))
+      .await
+  }
+
+  /// Reads a frame like [`WebSocket::read_frame`] and checks that it has the `expected` opcode,
+  /// e.g. for the first message of a protocol handshake.
+  ///
+  /// Fails with [`WebSocketError::UnexpectedOpcode`] otherwise; the frame is consumed either way.
+  pub async fn expect_opcode(
+    &mut self,
+    expected: OpCode,
+  ) -> Result<Frame<'f>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let frame = self.read_frame().await?;
+    if frame.opcode != expected {
+      return Err(WebSocketError::UnexpectedOpcode {
+        expected,
+        got: frame.opcode,
+      });
+    }
+    Ok(frame)
+  }
+
+  /// Reads frames like [`WebSocket::read_frame`] until one matches `predicate` and returns it, e.g.
+  /// to wait for the acknowledgment of a protocol handshake.
+  ///
+  /// Frames that do not match are discarded. Fails with [`WebSocketError::NotFound`] once
+  /// `max_skipped` frames have been discarded and the next one does not match either, and with
+  /// [`WebSocketError::ConnectionClosed`] if a close frame that does not match is read.
+  pub async fn read_frame_until<F>(
+    &mut self,
+    predicate: F,
+    max_skipped: usize,
+  ) -> Result<Frame<'f>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Fn(&Frame) -> bool,
+  {
+    for _ in 0..=max_skipped {
+      let frame = self.read_frame().await?;
+      if predicate(&frame) {
+        return Ok(frame);
+      }
+      if frame.opcode == OpCode::Close {
+        return Err(WebSocketError::ConnectionClosed);
+      }
+    }
+    Err(WebSocketError::NotFound)
+  }
+
+  /// Reads up to `n` frames with [`WebSocket::read_frame`] and returns them in order.
+  ///
+  /// Reading stops early after a close frame, which is the last frame returned. If a close frame
+  /// has already been received, no frames are read and the result is empty.
+  ///
+  /// This method is not cancel safe: frames that were read before the future is dropped are lost.
+  pub async fn read_n_frames(
+    &mut self,
+    n: usize,
+  ) -> Result<Vec<Frame<'f>>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    // `n` is only an upper bound, e.g. `usize::MAX` to read until a close frame.
+    let mut frames = Vec::new();
+    while frames.len() < n && !self.read_half.close_received {
+      frames.push(self.read_frame().await?);
+    }
+    Ok(frames)
+  }
+
+  /// Reads frames until a close frame has been read or `max` frames have been collected, e.g. to
+  /// gather the rest of a conversation in a test or a simple client script. `max` bounds the
+  /// collection in case the peer never closes.
+  ///
+  /// This is [`WebSocket::read_n_frames`] with the bound as a safety limit instead of a count.
+  pub async fn read_all_frames(
+    &mut self,
+    max: usize,
+  ) -> Result<Vec<Frame<'f>>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self.read_n_frames(max).await
+  }
+
+  /// Reads a frame from the stream like [`WebSocket::read_frame`], returning `Ok(None)` if no frame
+  /// arrives within `duration`.
+  ///
+  /// Since `read_frame` is cancel safe, a frame that was partially received when the timeout expired is
+  /// completed by the next read.
+  pub async fn read_frame_or_timeout(
+    &mut self,
+    duration: std::time::Duration,
+  ) -> Result<Option<Frame<'f>>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    match tokio::time::timeout(duration, self.read_frame()).await {
+      Ok(res) => res.map(Some),
+      Err(_) => Ok(None),
+    }
+  }
+
+  /// Reads the next event from the stream.
+  ///
+  /// Unlike [`WebSocket::read_frame`], ping frames answered by `auto_pong` are reported as
+  /// [`WebSocketEvent::Heartbeat`] and close frames are parsed into their code and reason.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{WebSocket, WebSocketEvent};
+  /// use tokio::net::TcpStream;
+  /// use anyhow::Result;
+  ///
+  /// async fn echo(
+  ///   ws: &mut WebSocket<TcpStream>
+  /// ) -> Result<()> {
+  ///   loop {
+  ///     match ws.read_event().await? {
+  ///       WebSocketEvent::Message(frame) => ws.write_frame(frame).await?,
+  ///       WebSocketEvent::Close(_, _) => break,
+  ///       _ => {}
+  ///     }
+  ///   }
+  ///   Ok(())
+  /// }
+  /// ```
+  pub async fn read_event(
+    &mut self,
+  ) -> Result<WebSocketEvent<'f>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    match self.read_next(false).await? {
+      Some(frame) => WebSocketEvent::from_frame(frame),
+      None => Ok(WebSocketEvent::Heartbeat),
+    }
+  }
+
+  /// Sends a ping with `payload` and waits for the matching pong, returning the round-trip time.
+  ///
+  /// Fails with [`WebSocketError::PongTimeout`] if no pong with the same payload arrives within
+  /// `timeout`. Pings from the peer are still answered while waiting, but any other frame received in
+  /// the meantime is discarded, so this is meant for connections that are otherwise idle. A close frame
+  /// fails with [`WebSocketError::ConnectionClosed`], and a payload longer than 125 bytes with
+  /// [`WebSocketError::PingFrameTooLarge`] before anything is sent.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::WebSocket;
+  /// use tokio::net::TcpStream;
+  /// use std::time::Duration;
+  /// use anyhow::Result;
+  ///
+  /// async fn latency(ws: &mut WebSocket<TcpStream>) -> Result<Duration> {
+  ///   Ok(ws.ping_rtt(b"rtt".to_vec(), Duration::from_secs(5)).await?)
+  /// }
+  /// ```
+  pub async fn ping_rtt(
+    &mut self,
+    payload: Vec<u8>,
+    timeout: std::time::Duration,
+  ) -> Result<std::time::Duration, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let start = std::time::Instant::now();
+    let ping = async {
+      self.send_ping(&payload).await?;
+      loop {
+        match self.read_next(true).await? {
+          Some(frame)
+            if frame.opcode == OpCode::Pong
+              && frame.payload == &payload[..] =>
+          {
+            return Ok(start.elapsed());
+          }
+          Some(frame) if frame.opcode == OpCode::Close => {
+            return Err(WebSocketError::ConnectionClosed);
+          }
+          _ => {}
+        }
+      }
+    };
+    tokio::time::timeout(timeout, ping)
+      .await
+      .map_err(|_| WebSocketError::PongTimeout)?
+  }
+
+  /// Performs the closing handshake and shuts down the stream.
+  ///
+  /// Sends a close frame with `code` and `reason`, unless one was already sent, then reads and discards
+  /// frames until the peer's close frame arrives or the stream ends. If the peer does not answer within
+  /// `timeout`, the handshake is abandoned and [`WebSocketError::CloseNotCompleted`] is returned. In
+  /// every case the stream is shut down afterwards, so the connection is closed when this returns.
+  ///
+  /// [`CloseCode::Status`] sends a close frame without a status code or reason. Fails with
+  /// [`WebSocketError::CloseReasonTooLarge`], without sending anything, if `reason` is longer than
+  /// 123 bytes, which would not fit in a control frame.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{CloseCode, WebSocket};
+  /// use tokio::net::TcpStream;
+  /// use std::time::Duration;
+  /// use anyhow::Result;
+  ///
+  /// async fn shutdown(mut ws: WebSocket<TcpStream>) -> Result<()> {
+  ///   ws.close_gracefully(CloseCode::Normal, "bye", Duration::from_secs(5)).await?;
+  ///   Ok(())
+  /// }
+  /// ```
+  pub async fn close_gracefully(
+    &mut self,
+    code: CloseCode,
+    reason: &str,
+    timeout: std::time::Duration,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    if reason.len() > 123 {
+      return Err(WebSocketError::CloseReasonTooLarge);
+    }
+    let handshake = async {
+      if !self.write_half.closed {
+        let payload = close::encode_close_payload(code, reason);
+        self
+          .write_half
+          .write_frame(&mut self.stream, Frame::close_raw(payload.into()))
+          .await?;
+      }
+      loop {
+        // Replies are not sent once our close frame is out, so the obligated send is dropped.
+        let (res, _) = self.read_half.read_frame_inner(&mut self.stream).await;
+        match res {
+          Ok(Some(frame)) if frame.opcode == OpCode::Close => break Ok(()),
+          Ok(_) => {}
+          Err(WebSocketError::UnexpectedEOF) => break Ok(()),
+          Err(e) => break Err(e),
+        }
+      }
+    };
+    let res = match tokio::time::timeout(timeout, handshake).await {
+      Ok(res) => res,
+      Err(_) => Err(WebSocketError::CloseNotCompleted),
+    };
+    // The peer may already have closed its end, which some streams report as an error here.
+    let _ = self.stream.shutdown().await;
+    res
+  }
+
+  /// Reads a single frame and sends any obligated reply. Returns `None` if the frame was handled
+  /// internally. With `keep_pongs`, pongs are returned even if `auto_pong_drop` is set.
+  async fn read_next(
+    &mut self,
+    keep_pongs: bool,
+  ) -> Result<Option<Frame<'f>>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let (res, obligated_send) = if keep_pongs {
+      self.read_half.read_frame_with_pongs(&mut self.stream).await
+    } else {
+      self.read_half.read_frame_inner(&mut self.stream).await
+    };
+    let is_closed = self.write_half.closed;
+    if let Some(frame) = obligated_send {
+      if !is_closed {
+        self
+          .write_half
+          .write_frame_with_priority(&mut self.stream, frame)
+          .await?;
+      }
+    }
+    match res? {
+      Some(frame) if is_closed && frame.opcode != OpCode::Close => {
+        Err(WebSocketError::ConnectionClosed)
+      }
+      res => Ok(res),
+    }
+  }
+}
+
+/// Writes the fragments of a message started with [`WebSocket::begin_fragmented_message`].
+///
+/// The first chunk is sent with the message opcode and the following ones as continuation frames.
+/// [`FragmentedSender::finish`] sends the final, empty fragment. If the sender is dropped before
+/// that, the message can't be finished anymore: every later data frame fails with
+/// [`WebSocketError::MessageInProgress`], so the connection should be closed. Control frames can
+/// still be written.
+pub struct FragmentedSender<'a, S> {
+  ws: &'a mut WebSocket<S>,
+  opcode: OpCode,
+}
+
+impl<S> FragmentedSender<'_, S>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  /// Writes `data` as the next fragment. Fails with [`WebSocketError::InvalidValue`] if the message
+  /// opcode is not [`OpCode::Text`] or [`OpCode::Binary`].
+  pub async fn write_chunk(
+    &mut self,
+    data: &[u8],
+  ) -> Result<(), WebSocketError> {
+    self.ws.write_chunk(self.opcode, data, false).await
+  }
+
+  /// Ends the message with an empty final fragment.
+  pub async fn finish(self) -> Result<(), WebSocketError> {
+    self.ws.write_chunk(self.opcode, &[], true).await
+  }
+}
+
+impl<S> Drop for FragmentedSender<'_, S> {
+  fn drop(&mut self) {
+    if self.ws.write_half.streaming {
+      self.ws.write_half.poisoned = true;
+    }
+  }
+}
+
+impl WebSocket<tokio::io::DuplexStream> {
+  /// Creates a pair of `WebSocket`s connected to each other through an in-memory [`tokio::io::duplex`] stream.
+  ///
+  /// This is useful to drive both ends of a connection in tests without network I/O.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{Frame, OpCode, Role, WebSocket};
+  ///
+  /// # #[tokio::main(flavor = "current_thread")]
+  /// # async fn main() -> anyhow::Result<()> {
+  /// let (mut server, mut client) =
+  ///   WebSocket::in_memory_pair(Role::Server, Role::Client);
+  ///
+  /// client.write_frame(Frame::text(b"hello"[..].into())).await?;
+  /// let frame = server.read_frame().await?;
+  /// assert_eq!(frame.opcode, OpCode::Text);
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub fn in_memory_pair(role_a: Role, role_b: Role) -> (Self, Self) {
+    let (a, b) = tokio::io::duplex(64 << 10);
+    (
+      Self::after_handshake(a, role_a),
+      Self::after_handshake(b, role_b),
+    )
+  }
+}
+
+#[cfg(all(unix, feature = "unix"))]
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "unix"))))]
+impl WebSocket<tokio::net::UnixStream> {
+  /// Creates a new `WebSocket` over a Unix domain socket.
+  ///
+  /// No HTTP upgrade is performed; both ends of the socket are expected to speak WebSocket framing
+  /// right away. This is useful for local IPC where no HTTP server is involved.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// use fastwebsockets::{WebSocket, Role};
+  /// use tokio::net::UnixStream;
+  /// use anyhow::Result;
+  ///
+  /// async fn connect() -> Result<WebSocket<UnixStream>> {
+  ///   let stream = UnixStream::connect("/tmp/fastwebsockets.sock").await?;
+  ///   Ok(WebSocket::over_unix(stream, Role::Client))
+  /// }
+  /// ```
+  pub fn over_unix(stream: tokio::net::UnixStream, role: Role) -> Self {
+    Self::after_handshake(stream, role)
+  }
+}
+
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+impl WebSocket<tokio::net::TcpStream> {
+  /// Sets the `TCP_NODELAY` option on the underlying socket.
+  ///
+  /// With Nagle's algorithm enabled, small frames such as pings, pongs and close frames can be held
+  /// back until earlier data is acknowledged, which adds up to a round trip of latency. Most WebSocket
+  /// servers want to disable it by setting `nodelay` to `true`.
+  pub fn set_nodelay(&mut self, nodelay: bool) -> std::io::Result<()> {
+    self.stream.set_nodelay(nodelay)
+  }
+
+  /// Returns the remote address of the underlying socket, e.g. to log which client sent a message.
+  pub fn peer_addr(&self) -> std::io::Result<std::net::SocketAddr> {
+    self.stream.peer_addr()
+  }
+
+  /// Returns the local address of the underlying socket.
+  pub fn local_addr(&self) -> std::io::Result<std::net::SocketAddr> {
+    self.stream.local_addr()
+  }
+}
+
+const MAX_HEADER_SIZE: usize = 14;
+const DEFAULT_READ_BUFFER_CAPACITY: usize = 8192;
+/// The largest number of buffers passed to a single vectored write, `UIO_MAXIOV` on Linux.
+const UIO_MAXIOV: usize = 1024;
+
+impl ReadHalf {
+  fn set_auto_close_code(&mut self, code: Option<CloseCode>) {
+    self.on_close = match code {
+      Some(code) => Some(Box::new(move |_, _| (code, String::new()))),
+      None => None,
+    };
+  }
+
+  pub fn after_handshake(role: Role) -> Self {
+    Self::with_buffer(
+      role,
+      BytesMut::with_capacity(DEFAULT_READ_BUFFER_CAPACITY),
+    )
+  }
+
+  pub(crate) fn with_buffer(role: Role, buffer: BytesMut) -> Self {
+    Self {
+      role,
+      auto_apply_mask: true,
+      auto_close: true,
+      auto_pong: true,
+      auto_pong_drop: false,
+      writev_threshold: 1024,
+      max_message_size: 64 << 20,
+      max_control_frame_size: 125,
+      buffer_capacity: buffer.capacity(),
+      buffer,
+      state: ReadState::Header,
+      close_received: false,
+      utf8_validation: Utf8Validation::Strict,
+      rate_limit: None,
+      extension: None,
+      on_ping: None,
+      on_close: None,
+      idle_timeout: None,
+      last_activity: None,
+    }
+  }
+
+  /// Attempt to read a single frame from from the incoming stream, returning any send obligations if
+  /// `auto_close` or `auto_pong` are enabled. Callers to this function are obligated to send the
+  /// frame in the latter half of the tuple if one is specified, unless the write half of this socket
+  /// has been closed.
+  ///
+  /// XXX: Do not expose this method to the public API.
+  pub(crate) async fn read_frame_inner<'f, S>(
+    &mut self,
+    stream: &mut S,
+  ) -> (Result<Option<Frame<'f>>, WebSocketError>, Option<Frame<'f>>)
+  where
+    S: AsyncRead + Unpin,
+  {
+    match self.read_frame_with_pongs(stream).await {
+      (Ok(Some(frame)), obligated_send)
+        if frame.opcode == OpCode::Pong && self.auto_pong_drop =>
+      {
+        (Ok(None), obligated_send)
+      }
+      res => res,
+    }
+  }
+
+  /// Like `read_frame_inner`, but returns pongs regardless of `auto_pong_drop`.
+  pub(crate) async fn read_frame_with_pongs<'f, S>(
+    &mut self,
+    stream: &mut S,
+  ) -> (Result<Option<Frame<'f>>, WebSocketError>, Option<Frame<'f>>)
+  where
+    S: AsyncRead + Unpin,
+  {
+    if let Some(rate_limit) = &self.rate_limit {
+      // Only wait between frames, so that a partially read frame is never held back.
+      if matches!(self.state, ReadState::Header) {
+        rate_limit.wait().await;
+      }
+    }
+
+    let res = match self.idle_timeout {
+      Some(timeout) => {
+        let deadline =
+          self.last_activity.unwrap_or_else(tokio::time::Instant::now)
+            + timeout;
+        let parse = self.parse_frame_header(stream);
+        match tokio::time::timeout_at(deadline, parse).await {
+          Ok(res) => res,
+          Err(_) => {
+            return (
+              Err(WebSocketError::IdleTimeout),
+              Some(Frame::close(1001, b"")),
+            )
+          }
+        }
+      }
+      None => self.parse_frame_header(stream).await,
+    };
+    let mut frame = match res {
+      Ok(frame) => frame,
+      Err(e) => return (Err(e), None),
+    };
+    if self.idle_timeout.is_some()
+      && (frame.opcode == OpCode::Ping || !frame.opcode.is_control())
+    {
+      self.last_activity = Some(tokio::time::Instant::now());
+    }
+    if let Some(rate_limit) = &mut self.rate_limit {
+      rate_limit.record(frame.payload.len());
+    }
+
+    if self.role == Role::Server && self.auto_apply_mask {
+      frame.unmask()
+    };
+
+    if let Some(extension) = self.extension.as_mut() {
+      if let Err(e) = extension.process(&mut frame) {
+        return (Err(e), None);
+      }
+    }
+
+    if frame.opcode == OpCode::Close {
+      self.close_received = true;
+    }
 
     match frame.opcode {
       OpCode::Close if self.auto_close => {
@@ -593,13 +2089,17 @@ impl ReadHalf {
               frame.payload[0..2].try_into().unwrap(),
             ));
 
+            let validate = self.utf8_validation != Utf8Validation::None;
+
             #[cfg(feature = "simd")]
-            if simdutf8::basic::from_utf8(&frame.payload[2..]).is_err() {
+            if validate
+              && simdutf8::basic::from_utf8(&frame.payload[2..]).is_err()
+            {
               return (Err(WebSocketError::InvalidUTF8), None);
             };
 
             #[cfg(not(feature = "simd"))]
-            if std::str::from_utf8(&frame.payload[2..]).is_err() {
+            if validate && std::str::from_utf8(&frame.payload[2..]).is_err() {
               return (Err(WebSocketError::InvalidUTF8), None);
             };
 
@@ -612,14 +2112,45 @@ impl ReadHalf {
           }
         };
 
-        let obligated_send = Frame::close_raw(frame.payload.to_owned().into());
+        let obligated_send = match self.on_close.as_mut() {
+          Some(on_close) => {
+            let (code, reason) = match frame.payload.len() {
+              0 => (CloseCode::Status, ""),
+              _ => (
+                CloseCode::from(u16::from_be_bytes([
+                  frame.payload[0],
+                  frame.payload[1],
+                ])),
+                // Validated above unless validation is disabled.
+                std::str::from_utf8(&frame.payload[2..]).unwrap_or_default(),
+              ),
+            };
+            let (code, reason) = on_close(code, reason);
+            if code != CloseCode::Status && reason.len() > 123 {
+              return (Err(WebSocketError::CloseReasonTooLarge), None);
+            }
+            Frame::close_raw(close::encode_close_payload(code, &reason).into())
+          }
+          None => Frame::close_raw(frame.payload.to_owned().into()),
+        };
         (Ok(Some(frame)), Some(obligated_send))
       }
-      OpCode::Ping if self.auto_pong => {
-        (Ok(None), Some(Frame::pong(frame.payload)))
-      }
+      OpCode::Ping if self.auto_pong => match self.on_ping.as_mut() {
+        Some(on_ping) => match on_ping(frame.payload.into()) {
+          Some(payload) if payload.len() > 125 => {
+            (Err(WebSocketError::PongFrameTooLarge), None)
+          }
+          pong => (Ok(None), pong.map(|payload| Frame::pong(payload.into()))),
+        },
+        None => (Ok(None), Some(Frame::pong(frame.payload))),
+      },
       OpCode::Text => {
-        if frame.fin && !frame.is_utf8() {
+        if frame.fin
+          && self.utf8_validation == Utf8Validation::Strict
+          && !frame.is_utf8()
+        {
+          #[cfg(feature = "tracing")]
+          tracing::error!("invalid utf-8 in text frame");
           (Err(WebSocketError::InvalidUTF8), None)
         } else {
           (Ok(Some(frame)), None)
@@ -629,113 +2160,368 @@ impl ReadHalf {
     }
   }
 
-  async fn parse_frame_header<'a, S>(
-    &mut self,
+  fn set_buffer_capacity(&mut self, capacity: usize) {
+    self.buffer_capacity = capacity;
+    self.reserve_buffer_capacity();
+  }
+
+  /// Grows the buffer back to `buffer_capacity`. Payloads are split off the front of the buffer, so
+  /// this reuses the original allocation once they have been dropped.
+  fn reserve_buffer_capacity(&mut self) {
+    if self.buffer.capacity() < self.buffer_capacity {
+      self
+        .buffer
+        .reserve(self.buffer_capacity - self.buffer.len());
+    }
+  }
+
+  /// Reads from `stream` until at least `n` bytes are buffered.
+  ///
+  /// Returns `Ok(n)` once the bytes are available and `Ok(0)` if the stream reached EOF cleanly, i.e.
+  /// between frames with nothing buffered. EOF in the middle of a frame is `WebSocketError::UnexpectedEOF`.
+  /// Reads failing with `ErrorKind::Interrupted` are retried.
+  async fn read_exact_or_eof<S>(
+    &mut self,
+    stream: &mut S,
+    n: usize,
+  ) -> Result<usize, WebSocketError>
+  where
+    S: AsyncRead + Unpin,
+  {
+    while self.buffer.remaining() < n {
+      self.reserve_buffer_capacity();
+      match stream.read_buf(&mut self.buffer).await {
+        Ok(0) => {
+          if self.buffer.is_empty() && matches!(self.state, ReadState::Header) {
+            return Ok(0);
+          }
+          return Err(WebSocketError::UnexpectedEOF);
+        }
+        Ok(_) => {}
+        // Streams are expected to retry interrupted reads themselves, but not all of them do.
+        Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+        Err(e) => return Err(e.into()),
+      }
+    }
+    Ok(n)
+  }
+
+  async fn parse_frame_header<'a, S>(
+    &mut self,
+    stream: &mut S,
+  ) -> Result<Frame<'a>, WebSocketError>
+  where
+    S: AsyncRead + Unpin,
+  {
+    if let ReadState::Header = self.state {
+      // Read the first two bytes. A peer that goes away without sending a close frame is still
+      // unexpected, even at a frame boundary.
+      if self.read_exact_or_eof(stream, 2).await? == 0 {
+        return Err(WebSocketError::UnexpectedEOF);
+      }
+
+      let fin = self.buffer[0] & 0b10000000 != 0;
+      let rsv = self.buffer[0] & 0b01110000;
+
+      // Reserved bits are left to the read extension, which is where extensions using them
+      // are implemented, but only the ones it claims.
+      let rsv_mask = self
+        .extension
+        .as_ref()
+        .map_or(0, |extension| extension.rsv_mask());
+      if rsv & !rsv_mask != 0 {
+        return Err(WebSocketError::ReservedBitsNotZero);
+      }
+
+      // Every opcode that fits in 4 bits is either defined or reserved by RFC 6455 section 5.2.
+      let raw_opcode = self.buffer[0] & 0b00001111;
+      let opcode = frame::OpCode::try_from(raw_opcode)
+        .map_err(|_| WebSocketError::ReservedOpcode(raw_opcode))?;
+      let masked = self.buffer[1] & 0b10000000 != 0;
+
+      let length_code = self.buffer[1] & 0x7F;
+      let extra = match length_code {
+        126 => 2,
+        127 => 8,
+        _ => 0,
+      };
+
+      // Nothing is consumed until the whole header is buffered, so it survives cancellation.
+      self
+        .read_exact_or_eof(stream, 2 + extra + masked as usize * 4)
+        .await?;
+      self.buffer.advance(2);
+
+      let payload_len: usize = match extra {
+        0 => usize::from(length_code),
+        2 => self.buffer.get_u16() as usize,
+        #[cfg(any(target_pointer_width = "64", target_pointer_width = "128"))]
+        8 => self.buffer.get_u64() as usize,
+        // On 32bit systems, usize is only 4bytes wide so we must check for usize overflowing
+        #[cfg(any(
+          target_pointer_width = "8",
+          target_pointer_width = "16",
+          target_pointer_width = "32"
+        ))]
+        8 => match usize::try_from(self.buffer.get_u64()) {
+          Ok(length) => length,
+          Err(_) => return Err(WebSocketError::FrameTooLarge),
+        },
+        _ => unreachable!(),
+      };
+
+      let mask = if masked {
+        Some(self.buffer.get_u32().to_be_bytes())
+      } else {
+        None
+      };
+
+      if opcode.is_control() && !fin {
+        return Err(WebSocketError::ControlFrameFragmented);
+      }
+
+      if opcode.is_control() {
+        if payload_len > self.max_control_frame_size {
+          return Err(match opcode {
+            OpCode::Ping => WebSocketError::PingFrameTooLarge,
+            _ => WebSocketError::FrameTooLarge,
+          });
+        }
+      } else if payload_len >= self.max_message_size {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(len = payload_len, "frame exceeds max_message_size");
+        return Err(WebSocketError::FrameTooLarge);
+      }
+
+      self.state = ReadState::Payload {
+        fin,
+        rsv,
+        opcode,
+        mask,
+        payload_len,
+      };
+    }
+
+    let ReadState::Payload {
+      fin,
+      rsv,
+      opcode,
+      mask,
+      payload_len,
+    } = self.state
+    else {
+      unreachable!()
+    };
+
+    // Reserve a bit more to try to get next frame header and avoid a syscall to read it next time
+    self.buffer.reserve(payload_len + MAX_HEADER_SIZE);
+    self.read_exact_or_eof(stream, payload_len).await?;
+
+    // if we read too much it will stay in the buffer, for the next call to this method
+    let payload = self.buffer.split_to(payload_len);
+    self.state = ReadState::Header;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?opcode, fin, len = payload_len, "read frame");
+    let frame =
+      Frame::new(fin, opcode, mask, Payload::Bytes(payload)).with_rsv(rsv);
+    Ok(frame)
+  }
+}
+
+impl WriteHalf {
+  pub fn after_handshake(role: Role) -> Self {
+    Self::with_buffer(role, Vec::with_capacity(2))
+  }
+
+  pub(crate) fn with_buffer(role: Role, write_buffer: Vec<u8>) -> Self {
+    Self {
+      role,
+      closed: false,
+      auto_apply_mask: true,
+      vectored: true,
+      writev_threshold: 1024,
+      write_buffer,
+      pending: 0..0,
+      priority_write_buffer: Vec::new(),
+      rate_limit: None,
+      extension: None,
+      streaming: false,
+      poisoned: false,
+    }
+  }
+
+  /// Masks the frame if needed and updates the close state before it is written.
+  pub(crate) fn prepare_frame(
+    &mut self,
+    frame: &mut Frame,
+  ) -> Result<(), WebSocketError> {
+    // A new message can't start inside the one streamed with `write_chunk`, and an abandoned one
+    // can't be continued either.
+    if !frame.opcode.is_control()
+      && (self.poisoned
+        || self.streaming && frame.opcode != OpCode::Continuation)
+    {
+      return Err(WebSocketError::MessageInProgress);
+    }
+
+    if let Some(extension) = self.extension.as_mut() {
+      extension.process(frame)?;
+    }
+
+    if self.role == Role::Client && self.auto_apply_mask {
+      frame.mask();
+    }
+
+    if frame.opcode == OpCode::Close {
+      self.closed = true;
+    } else if self.closed {
+      return Err(WebSocketError::ConnectionClosed);
+    }
+
+    Ok(())
+  }
+
+  /// Writes a frame to the provided stream.
+  pub async fn write_frame<'a, S>(
+    &'a mut self,
     stream: &mut S,
-  ) -> Result<Frame<'a>, WebSocketError>
+    mut frame: Frame<'a>,
+  ) -> Result<(), WebSocketError>
   where
-    S: AsyncRead + Unpin,
+    S: AsyncWrite + Unpin,
   {
-    macro_rules! eof {
-      ($n:expr) => {{
-        if $n == 0 {
-          return Err(WebSocketError::UnexpectedEOF);
-        }
-      }};
+    self.flush_pending(stream).await?;
+    if let Some(rate_limit) = &mut self.rate_limit {
+      rate_limit.wait().await;
+      rate_limit.record(frame.payload.len());
     }
+    self.prepare_frame(&mut frame)?;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+      opcode = ?frame.opcode,
+      fin = frame.fin,
+      len = frame.payload.len(),
+      "write frame"
+    );
 
-    // Read the first two bytes
-    while self.buffer.remaining() < 2 {
-      eof!(stream.read_buf(&mut self.buffer).await?);
+    if self.vectored && frame.payload.len() > self.writev_threshold {
+      frame.writev(stream).await?;
+    } else {
+      self.pending = 0..frame.write(&mut self.write_buffer).len();
+      self.flush_pending(stream).await?;
     }
 
-    let fin = self.buffer[0] & 0b10000000 != 0;
-    let rsv1 = self.buffer[0] & 0b01000000 != 0;
-    let rsv2 = self.buffer[0] & 0b00100000 != 0;
-    let rsv3 = self.buffer[0] & 0b00010000 != 0;
+    Ok(())
+  }
 
-    if rsv1 || rsv2 || rsv3 {
-      return Err(WebSocketError::ReservedBitsNotZero);
+  pub(crate) async fn write_frame_vectored<'a, S>(
+    &'a mut self,
+    stream: &mut S,
+    mut frame: Frame<'a>,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    self.flush_pending(stream).await?;
+    if let Some(rate_limit) = &mut self.rate_limit {
+      rate_limit.wait().await;
+      rate_limit.record(frame.payload.len());
     }
+    self.prepare_frame(&mut frame)?;
+    frame.writev(stream).await?;
+    Ok(())
+  }
 
-    let opcode = frame::OpCode::try_from(self.buffer[0] & 0b00001111)?;
-    let masked = self.buffer[1] & 0b10000000 != 0;
-
-    let length_code = self.buffer[1] & 0x7F;
-    let extra = match length_code {
-      126 => 2,
-      127 => 8,
-      _ => 0,
-    };
-
-    self.buffer.advance(2);
-    while self.buffer.remaining() < extra + masked as usize * 4 {
-      eof!(stream.read_buf(&mut self.buffer).await?);
-    }
-
-    let payload_len: usize = match extra {
-      0 => usize::from(length_code),
-      2 => self.buffer.get_u16() as usize,
-      #[cfg(any(target_pointer_width = "64", target_pointer_width = "128"))]
-      8 => self.buffer.get_u64() as usize,
-      // On 32bit systems, usize is only 4bytes wide so we must check for usize overflowing
-      #[cfg(any(
-        target_pointer_width = "8",
-        target_pointer_width = "16",
-        target_pointer_width = "32"
-      ))]
-      8 => match usize::try_from(self.buffer.get_u64()) {
-        Ok(length) => length,
-        Err(_) => return Err(WebSocketError::FrameTooLarge),
-      },
-      _ => unreachable!(),
-    };
-
-    let mask = if masked {
-      Some(self.buffer.get_u32().to_be_bytes())
-    } else {
-      None
-    };
-
-    if frame::is_control(opcode) && !fin {
-      return Err(WebSocketError::ControlFrameFragmented);
+  pub(crate) async fn write_batch<S>(
+    &mut self,
+    stream: &mut S,
+    frames: &[(&[u8], OpCode)],
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    // Check the batch up front, so that an invalid frame does not leave the frames before it
+    // prepared but unsent.
+    let mut closed = self.closed;
+    for &(_, opcode) in frames {
+      if closed {
+        return Err(WebSocketError::ConnectionClosed);
+      }
+      match opcode {
+        OpCode::Continuation => return Err(WebSocketError::InvalidValue),
+        OpCode::Text | OpCode::Binary if self.streaming || self.poisoned => {
+          return Err(WebSocketError::MessageInProgress)
+        }
+        OpCode::Close => closed = true,
+        _ => {}
+      }
     }
 
-    if opcode == OpCode::Ping && payload_len > 125 {
-      return Err(WebSocketError::PingFrameTooLarge);
+    self.flush_pending(stream).await?;
+    if let Some(rate_limit) = &mut self.rate_limit {
+      rate_limit.wait().await;
     }
 
-    if payload_len >= self.max_message_size {
-      return Err(WebSocketError::FrameTooLarge);
+    let was_closed = self.closed;
+    let mut prepared = Vec::with_capacity(frames.len());
+    let mut heads = Vec::with_capacity(frames.len() * frame::MAX_HEAD_SIZE);
+    let mut head_ends = Vec::with_capacity(frames.len());
+    for &(payload, opcode) in frames {
+      let mut frame =
+        Frame::new(true, opcode, None, Payload::Borrowed(payload));
+      if let Err(e) = self.prepare_frame(&mut frame) {
+        // Nothing has been written, so a close frame earlier in the batch does not count.
+        self.closed = was_closed;
+        return Err(e);
+      }
+      let mut head = [0; frame::MAX_HEAD_SIZE];
+      let size = frame.fmt_head(&mut head);
+      heads.extend_from_slice(&head[..size]);
+      head_ends.push(heads.len());
+      prepared.push(frame);
     }
-
-    // Reserve a bit more to try to get next frame header and avoid a syscall to read it next time
-    self.buffer.reserve(payload_len + MAX_HEADER_SIZE);
-    while payload_len > self.buffer.remaining() {
-      eof!(stream.read_buf(&mut self.buffer).await?);
+    if let Some(rate_limit) = &mut self.rate_limit {
+      rate_limit.record(frames.iter().map(|(payload, _)| payload.len()).sum());
     }
 
-    // if we read too much it will stay in the buffer, for the next call to this method
-    let payload = self.buffer.split_to(payload_len);
-    let frame = Frame::new(fin, opcode, mask, Payload::Bytes(payload));
-    Ok(frame)
-  }
-}
+    let mut segments = Vec::with_capacity(frames.len() * 2);
+    let mut head_start = 0;
+    for (frame, head_end) in prepared.iter().zip(head_ends) {
+      segments.push(&heads[head_start..head_end]);
+      head_start = head_end;
+      if !frame.payload.is_empty() {
+        segments.push(&frame.payload[..]);
+      }
+    }
 
-impl WriteHalf {
-  pub fn after_handshake(role: Role) -> Self {
-    Self {
-      role,
-      closed: false,
-      auto_apply_mask: true,
-      vectored: true,
-      writev_threshold: 1024,
-      write_buffer: Vec::with_capacity(2),
+    let (mut index, mut offset) = (0, 0);
+    while index < segments.len() {
+      let slices = std::iter::once(&segments[index][offset..])
+        .chain(segments[index + 1..].iter().copied())
+        .take(UIO_MAXIOV)
+        .map(std::io::IoSlice::new)
+        .collect::<Vec<_>>();
+      let mut n = stream.write_vectored(&slices).await?;
+      if n == 0 {
+        return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+      }
+      while n > 0 {
+        let remaining = segments[index].len() - offset;
+        if n < remaining {
+          offset += n;
+          break;
+        }
+        n -= remaining;
+        index += 1;
+        offset = 0;
+      }
     }
+    Ok(())
   }
 
-  /// Writes a frame to the provided stream.
-  pub async fn write_frame<'a, S>(
+  /// Writes a frame through `write_buffer` so that, if the future is dropped, the unwritten rest of
+  /// the frame is kept and sent before the next frame.
+  pub(crate) async fn write_frame_buffered<'a, S>(
     &'a mut self,
     stream: &mut S,
     mut frame: Frame<'a>,
@@ -743,23 +2529,94 @@ impl WriteHalf {
   where
     S: AsyncWrite + Unpin,
   {
-    if self.role == Role::Client && self.auto_apply_mask {
-      frame.mask();
+    self.flush_pending(stream).await?;
+    if let Some(rate_limit) = &mut self.rate_limit {
+      rate_limit.wait().await;
+      rate_limit.record(frame.payload.len());
     }
+    self.prepare_frame(&mut frame)?;
+    self.pending = 0..frame.write(&mut self.write_buffer).len();
+    self.flush_pending(stream).await
+  }
 
-    if frame.opcode == OpCode::Close {
-      self.closed = true;
-    } else if self.closed {
-      return Err(WebSocketError::ConnectionClosed);
+  pub(crate) async fn flush_pending<S>(
+    &mut self,
+    stream: &mut S,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    std::future::poll_fn(|cx| self.poll_flush_pending(stream, cx)).await
+  }
+
+  /// Writes the rest of a cancelled write and then the queued frames, see `flush_pending`.
+  pub(crate) fn poll_flush_pending<S>(
+    &mut self,
+    stream: &mut S,
+    cx: &mut std::task::Context<'_>,
+  ) -> std::task::Poll<Result<(), WebSocketError>>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    loop {
+      while !self.pending.is_empty() {
+        let n = std::task::ready!(std::pin::Pin::new(&mut *stream)
+          .poll_write(cx, &self.write_buffer[self.pending.clone()]))?;
+        if n == 0 {
+          return std::task::Poll::Ready(Err(
+            std::io::Error::from(std::io::ErrorKind::WriteZero).into(),
+          ));
+        }
+        self.pending.start += n;
+      }
+
+      if self.priority_write_buffer.is_empty() {
+        return std::task::Poll::Ready(Ok(()));
+      }
+      // A frame can't be interrupted on the wire, so queued control frames go right after the
+      // unwritten tail of the previous frame.
+      std::mem::swap(&mut self.write_buffer, &mut self.priority_write_buffer);
+      self.pending = 0..self.write_buffer.len();
+      self.priority_write_buffer.clear();
     }
+  }
 
-    if self.vectored && frame.payload.len() > self.writev_threshold {
-      frame.writev(stream).await?;
-    } else {
-      let text = frame.write(&mut self.write_buffer);
-      stream.write_all(text).await?;
+  /// Queues a control frame ahead of any data frame that is written later, then writes it.
+  ///
+  /// If the future is dropped, e.g. because a write timed out, the queued frame stays queued and is
+  /// written before the next data frame. Data frames are written like `write_frame`.
+  pub async fn write_frame_with_priority<'a, S>(
+    &'a mut self,
+    stream: &mut S,
+    frame: Frame<'a>,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    if !frame.opcode.is_control() {
+      return self.write_frame(stream, frame).await;
     }
 
+    self.queue_frame(frame)?;
+    self.flush_pending(stream).await
+  }
+
+  /// Prepares `frame` and queues it behind the frames that are already queued, to be written by
+  /// `flush_pending` or `poll_flush_pending`.
+  pub(crate) fn queue_frame(
+    &mut self,
+    mut frame: Frame,
+  ) -> Result<(), WebSocketError> {
+    self.prepare_frame(&mut frame)?;
+    let start = self.priority_write_buffer.len();
+    self
+      .priority_write_buffer
+      .resize(start + frame.payload.len() + frame::MAX_HEAD_SIZE, 0);
+    let size = frame.fmt_head(&mut self.priority_write_buffer[start..]);
+    let end = start + size + frame.payload.len();
+    self.priority_write_buffer[start + size..end]
+      .copy_from_slice(&frame.payload);
+    self.priority_write_buffer.truncate(end);
     Ok(())
   }
 }
@@ -792,4 +2649,1308 @@ mod tests {
     }
     assert_unsync::<WebSocket<tokio::net::TcpStream>>();
   };
+
+  #[tokio::test]
+  async fn read_frame_eof() {
+    let mut bytes = Vec::new();
+    Frame::binary(vec![7; 200].into()).write(&mut bytes);
+
+    // EOF before the header, inside the header and inside the payload.
+    for len in [0, 1, 3, 100] {
+      let (mut client, server) = tokio::io::duplex(1024);
+      let mut ws = WebSocket::after_handshake(server, Role::Server);
+      client.write_all(&bytes[..len]).await.unwrap();
+      drop(client);
+
+      assert!(matches!(
+        ws.read_frame().await,
+        Err(WebSocketError::UnexpectedEOF)
+      ));
+    }
+  }
+
+  #[tokio::test]
+  async fn read_frame_is_cancel_safe() {
+    use std::time::Duration;
+
+    let (mut client, server) = tokio::io::duplex(1024);
+    let mut ws = WebSocket::after_handshake(server, Role::Server);
+
+    let mut bytes = Vec::new();
+    Frame::binary(vec![7; 200].into()).write(&mut bytes);
+    let bytes = &bytes[..2 + 2 + 200];
+
+    // Cancel once inside the extended length and once inside the payload.
+    for chunk in [&bytes[..3], &bytes[3..100]] {
+      client.write_all(chunk).await.unwrap();
+      let res =
+        tokio::time::timeout(Duration::from_millis(10), ws.read_frame()).await;
+      assert!(res.is_err());
+    }
+
+    client.write_all(&bytes[100..]).await.unwrap();
+    let frame = ws.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Binary);
+    assert_eq!(&frame.payload[..], &[7; 200][..]);
+  }
+
+  #[tokio::test]
+  async fn read_frame_reserved_opcode() {
+    for opcode in [0x3, 0x7, 0xB, 0xF] {
+      let (mut client, server) = tokio::io::duplex(1024);
+      let mut ws = WebSocket::after_handshake(server, Role::Server);
+      client.write_all(&[0x80 | opcode, 0x00]).await.unwrap();
+
+      assert!(matches!(
+        ws.read_frame().await,
+        Err(WebSocketError::ReservedOpcode(op)) if op == opcode
+      ));
+    }
+  }
+
+  #[tokio::test]
+  async fn ping_rtt() {
+    use std::time::Duration;
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let peer = tokio::spawn(async move {
+      // Answers the ping through auto_pong, then stops reading.
+      assert!(matches!(
+        server.read_event().await,
+        Ok(WebSocketEvent::Heartbeat)
+      ));
+      server
+    });
+    let rtt = client
+      .ping_rtt(b"rtt".to_vec(), Duration::from_secs(5))
+      .await;
+    assert!(rtt.is_ok());
+
+    let _server = peer.await.unwrap();
+    let res = client
+      .ping_rtt(b"rtt".to_vec(), Duration::from_millis(10))
+      .await;
+    assert!(matches!(res, Err(WebSocketError::PongTimeout)));
+
+    let res = client.ping_rtt(vec![0; 126], Duration::from_secs(5)).await;
+    assert!(matches!(res, Err(WebSocketError::PingFrameTooLarge)));
+  }
+
+  #[tokio::test]
+  async fn control_frames_ignore_max_message_size() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_max_message_size(16);
+
+    let reason = [b'a'; 100];
+    client
+      .write_frame(Frame::close(1000, &reason))
+      .await
+      .unwrap();
+    let frame = server.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Close);
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_max_control_frame_size(8);
+    client
+      .write_frame(Frame::new(true, OpCode::Ping, None, vec![0; 9].into()))
+      .await
+      .unwrap();
+    assert!(matches!(
+      server.read_frame().await,
+      Err(WebSocketError::PingFrameTooLarge)
+    ));
+  }
+
+  #[tokio::test]
+  async fn read_frame_retries_interrupted() {
+    use std::pin::Pin;
+    use std::task::Context;
+    use std::task::Poll;
+
+    struct Interrupting {
+      bytes: Vec<u8>,
+      interrupted: bool,
+    }
+
+    impl AsyncRead for Interrupting {
+      fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+      ) -> Poll<std::io::Result<()>> {
+        if !self.interrupted {
+          self.interrupted = true;
+          return Poll::Ready(Err(std::io::ErrorKind::Interrupted.into()));
+        }
+        let n = self.bytes.len().min(buf.remaining());
+        buf.put_slice(&self.bytes[..n]);
+        self.bytes.drain(..n);
+        Poll::Ready(Ok(()))
+      }
+    }
+
+    impl AsyncWrite for Interrupting {
+      fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+      ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+      }
+
+      fn poll_flush(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+      ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+      }
+
+      fn poll_shutdown(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+      ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+      }
+    }
+
+    let mut bytes = Vec::new();
+    Frame::text(b"hello"[..].into()).write(&mut bytes);
+    let stream = Interrupting {
+      bytes,
+      interrupted: false,
+    };
+    let mut ws = WebSocket::after_handshake(stream, Role::Client);
+    let frame = ws.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Text);
+    assert_eq!(&frame.payload[..], b"hello");
+  }
+
+  #[tokio::test]
+  async fn after_handshake_with_buffers() {
+    let (client, server) = tokio::io::duplex(1024);
+    let mut client = WebSocket::after_handshake_with_buffers(
+      client,
+      Role::Client,
+      b"stale"[..].into(),
+      BytesMut::with_capacity(64),
+    );
+    let mut server = WebSocket::after_handshake(server, Role::Server);
+
+    client
+      .write_frame(Frame::text(b"hello"[..].into()))
+      .await
+      .unwrap();
+    let frame = server.read_frame().await.unwrap();
+    assert_eq!(&frame.payload[..], b"hello");
+
+    server.write_frame(frame).await.unwrap();
+    let frame = client.read_frame().await.unwrap();
+    assert_eq!(&frame.payload[..], b"hello");
+    assert_eq!(client.read_half.buffer_capacity, 64);
+  }
+
+  #[tokio::test]
+  async fn extension_registry() {
+    struct Xor;
+
+    impl ExtensionRegistry for Xor {
+      fn on_receive(
+        &mut self,
+        _opcode: OpCode,
+        payload: &mut Vec<u8>,
+      ) -> Result<(), WebSocketError> {
+        payload.iter_mut().for_each(|b| *b ^= 0xff);
+        Ok(())
+      }
+
+      fn on_send(
+        &mut self,
+        _opcode: OpCode,
+        payload: &mut Vec<u8>,
+      ) -> Result<(), WebSocketError> {
+        payload.iter_mut().for_each(|b| *b ^= 0xff);
+        payload.push(0);
+        Ok(())
+      }
+    }
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    client.set_extension(Box::new(Xor));
+
+    client
+      .write_frame(Frame::binary(vec![1, 2].into()))
+      .await
+      .unwrap();
+    let frame = server.read_frame().await.unwrap();
+    assert_eq!(&frame.payload[..], &[0xfe, 0xfd, 0]);
+
+    server.write_frame(frame).await.unwrap();
+    let frame = client.read_frame().await.unwrap();
+    assert_eq!(&frame.payload[..], &[1, 2, 0xff]);
+  }
+
+  #[tokio::test]
+  async fn write_frame_timeout_resumes() {
+    use std::time::Duration;
+
+    let (client, server) = tokio::io::duplex(64);
+    let mut client = WebSocket::after_handshake(client, Role::Client);
+    let mut server = WebSocket::after_handshake(server, Role::Server);
+
+    // The frame does not fit in the duplex buffer while nobody reads.
+    let res = client
+      .write_frame_timeout(
+        Frame::binary(vec![1; 200].into()),
+        Duration::from_millis(10),
+      )
+      .await;
+    assert!(matches!(res, Err(WebSocketError::WriteTimeout)));
+
+    let reader = tokio::spawn(async move {
+      let first = server.read_frame().await.unwrap();
+      assert_eq!(&first.payload[..], &[1; 200][..]);
+      let second = server.read_frame().await.unwrap();
+      assert_eq!(&second.payload[..], b"next");
+    });
+    client
+      .write_frame(Frame::text(b"next"[..].into()))
+      .await
+      .unwrap();
+    reader.await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn close_gracefully() {
+    use std::time::Duration;
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let peer = tokio::spawn(async move {
+      server
+        .write_frame(Frame::text(b"pending"[..].into()))
+        .await
+        .unwrap();
+      let frame = server.read_frame().await.unwrap();
+      assert_eq!(frame.opcode, OpCode::Close);
+      assert_eq!(&frame.payload[2..], b"bye");
+    });
+
+    client
+      .close_gracefully(CloseCode::Normal, "bye", Duration::from_secs(5))
+      .await
+      .unwrap();
+    assert!(client.is_closed());
+    peer.await.unwrap();
+
+    // A peer that never answers.
+    let (_server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    assert!(matches!(
+      client
+        .close_gracefully(CloseCode::Away, "", Duration::from_millis(10))
+        .await,
+      Err(WebSocketError::CloseNotCompleted)
+    ));
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    assert!(matches!(
+      client
+        .close_gracefully(
+          CloseCode::Normal,
+          &"x".repeat(124),
+          Duration::from_secs(5)
+        )
+        .await,
+      Err(WebSocketError::CloseReasonTooLarge)
+    ));
+    assert!(!client.is_closed());
+
+    // `Status` is sent as an empty close frame instead of the reserved code 1005.
+    let peer = tokio::spawn(async move {
+      let frame = server.read_frame().await.unwrap();
+      assert_eq!(frame.opcode, OpCode::Close);
+      assert!(frame.payload.is_empty());
+    });
+    client
+      .close_gracefully(CloseCode::Status, "", Duration::from_secs(5))
+      .await
+      .unwrap();
+    peer.await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn read_frame_or_timeout() {
+    use std::time::Duration;
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let res = server
+      .read_frame_or_timeout(Duration::from_millis(10))
+      .await;
+    assert!(matches!(res, Ok(None)));
+
+    client
+      .write_frame(Frame::text(b"hello"[..].into()))
+      .await
+      .unwrap();
+    let frame = server
+      .read_frame_or_timeout(Duration::from_secs(5))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(&frame.payload[..], b"hello");
+  }
+
+  #[tokio::test]
+  async fn close_state() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    assert!(!client.is_closing() && !client.is_closed());
+
+    client.write_frame(Frame::close(1000, b"")).await.unwrap();
+    assert!(client.is_closing() && !client.is_closed());
+
+    // auto_close echoes the close frame.
+    server.read_frame().await.unwrap();
+    assert!(!server.is_closing() && server.is_closed());
+
+    client.read_frame().await.unwrap();
+    assert!(!client.is_closing() && client.is_closed());
+  }
+
+  #[tokio::test]
+  async fn priority_frames_precede_data() {
+    use std::time::Duration;
+
+    let (client, server) = tokio::io::duplex(64);
+    let mut client = WebSocket::after_handshake(client, Role::Client);
+    let mut server = WebSocket::after_handshake(server, Role::Server);
+    server.set_auto_pong(false);
+
+    let res = client
+      .write_frame_timeout(
+        Frame::binary(vec![1; 200].into()),
+        Duration::from_millis(10),
+      )
+      .await;
+    assert!(matches!(res, Err(WebSocketError::WriteTimeout)));
+    let ping = Frame::new(true, OpCode::Ping, None, b"ping"[..].into());
+    let res = tokio::time::timeout(
+      Duration::from_millis(10),
+      client.write_frame_with_priority(ping),
+    )
+    .await;
+    assert!(res.is_err());
+
+    let reader = tokio::spawn(async move {
+      let mut opcodes = Vec::new();
+      for _ in 0..3 {
+        opcodes.push(server.read_frame().await.unwrap().opcode);
+      }
+      opcodes
+    });
+    client
+      .write_frame(Frame::text(b"next"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(
+      reader.await.unwrap(),
+      [OpCode::Binary, OpCode::Ping, OpCode::Text]
+    );
+  }
+
+  #[tokio::test]
+  async fn auto_pong_writes_pong() {
+    use crate::test_utils::MockStream;
+
+    let ping = Frame::new(true, OpCode::Ping, None, b"abc"[..].into());
+    let mut ws = WebSocket::after_handshake(
+      MockStream::with_data(ping.to_vec()),
+      Role::Server,
+    );
+    assert!(matches!(
+      ws.read_event().await,
+      Ok(WebSocketEvent::Heartbeat)
+    ));
+
+    let pong = Frame::pong(b"abc"[..].into()).to_vec();
+    assert_eq!(ws.into_inner().written(), &pong[..]);
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn read_rate_limit() {
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_read_rate_limit(1000);
+
+    for _ in 0..3 {
+      client
+        .write_frame(Frame::binary(vec![0; 500].into()))
+        .await
+        .unwrap();
+    }
+
+    let start = Instant::now();
+    for _ in 0..3 {
+      server.read_frame().await.unwrap();
+    }
+    // The first frame is free; the other two wait for 500 bytes each to drain.
+    assert_eq!(start.elapsed(), Duration::from_secs(1));
+  }
+
+  #[tokio::test]
+  async fn close_handler() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_close_handler(|code, reason| {
+      assert_eq!(code, CloseCode::Protocol);
+      assert_eq!(reason, "oops");
+      (CloseCode::Policy, "policy".to_owned())
+    });
+
+    client
+      .write_frame(Frame::close(1002, b"oops"))
+      .await
+      .unwrap();
+    server.read_frame().await.unwrap();
+
+    let frame = client.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Close);
+    assert_eq!(&frame.payload[..2], &1008u16.to_be_bytes());
+    assert_eq!(&frame.payload[2..], b"policy");
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_close_handler(|_, _| (CloseCode::Normal, "x".repeat(124)));
+    client.write_frame(Frame::close(1000, b"")).await.unwrap();
+    assert!(matches!(
+      server.read_frame().await,
+      Err(WebSocketError::CloseReasonTooLarge)
+    ));
+    assert!(!server.write_half.closed);
+  }
+
+  #[tokio::test]
+  async fn take_and_prepend_read_buffer() {
+    let mut input = Frame::text(b"one"[..].into()).to_vec();
+    input.extend(Frame::text(b"two"[..].into()).to_vec());
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input),
+      Role::Client,
+    );
+
+    assert_eq!(ws.take_read_buffer(), None);
+    assert_eq!(ws.read_frame().await.unwrap().payload, b"one");
+    let rest = ws.take_read_buffer().unwrap();
+    assert_eq!(rest, Frame::text(b"two"[..].into()).to_vec());
+
+    let mut first = Frame::text(b"zero"[..].into()).to_vec();
+    let split = first.len() - 2;
+    let tail = first.split_off(split);
+    ws.prepend_read_buffer(rest);
+    ws.prepend_read_buffer(tail);
+    ws.prepend_read_buffer(first);
+    assert_eq!(ws.read_frame().await.unwrap().payload, b"zero");
+    assert_eq!(ws.read_frame().await.unwrap().payload, b"two");
+  }
+
+  #[tokio::test]
+  async fn utf8_validation() {
+    let invalid = || Frame::text(vec![0xff, 0xfe].into()).to_vec();
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(invalid()),
+      Role::Client,
+    );
+    assert!(matches!(
+      ws.read_frame().await,
+      Err(WebSocketError::InvalidUTF8)
+    ));
+
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(invalid()),
+      Role::Client,
+    );
+    ws.set_utf8_validation(Utf8Validation::Relaxed);
+    assert_eq!(ws.read_frame().await.unwrap().payload, &[0xff, 0xfe]);
+
+    // Reassembled messages follow the mode of the wrapped connection.
+    let mut input =
+      Frame::new(false, OpCode::Text, None, vec![0xff].into()).to_vec();
+    input.extend(
+      Frame::new(true, OpCode::Continuation, None, vec![0xfe].into()).to_vec(),
+    );
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input.clone()),
+      Role::Client,
+    );
+    ws.set_utf8_validation(Utf8Validation::Relaxed);
+    let mut ws = FragmentCollector::new(ws);
+    assert_eq!(ws.read_frame().await.unwrap().payload, &[0xff, 0xfe]);
+
+    let mut ws = FragmentCollector::new(WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input),
+      Role::Client,
+    ));
+    assert!(matches!(
+      ws.read_frame().await,
+      Err(WebSocketError::InvalidUTF8)
+    ));
+
+    // Close reasons are only skipped with `None`.
+    let close = || Frame::close_raw(vec![0x03, 0xe8, 0xff].into()).to_vec();
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(close()),
+      Role::Client,
+    );
+    ws.set_utf8_validation(Utf8Validation::Relaxed);
+    assert!(matches!(
+      ws.read_frame().await,
+      Err(WebSocketError::InvalidUTF8)
+    ));
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(close()),
+      Role::Client,
+    );
+    ws.set_utf8_validation(Utf8Validation::None);
+    assert_eq!(ws.read_frame().await.unwrap().opcode, OpCode::Close);
+  }
+
+  #[tokio::test]
+  async fn read_n_frames() {
+    let mut input = Vec::new();
+    for payload in [&b"a"[..], b"b", b"c"] {
+      input.extend(Frame::text(payload.into()).to_vec());
+    }
+    input.extend(Frame::close(1000, b"").to_vec());
+    input.extend(Frame::text(b"d"[..].into()).to_vec());
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input),
+      Role::Client,
+    );
+
+    let frames = ws.read_n_frames(2).await.unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1].payload, b"b");
+
+    let frames = ws.read_n_frames(5).await.unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1].opcode, OpCode::Close);
+
+    assert!(ws.read_n_frames(5).await.unwrap().is_empty());
+
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(
+        Frame::close(1000, b"").to_vec(),
+      ),
+      Role::Client,
+    );
+    let frames = ws.read_n_frames(usize::MAX).await.unwrap();
+    assert_eq!(frames.len(), 1);
+  }
+
+  #[cfg(feature = "tcp")]
+  #[tokio::test]
+  async fn tcp_addrs() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+
+    let client = WebSocket::after_handshake(client, Role::Client);
+    let server = WebSocket::after_handshake(server, Role::Server);
+    assert_eq!(client.peer_addr().unwrap(), addr);
+    assert_eq!(server.peer_addr().unwrap(), client.local_addr().unwrap());
+  }
+
+  #[tokio::test]
+  async fn write_chunk() {
+    let (mut a, b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let mut b = FragmentCollector::new(b);
+
+    assert!(matches!(
+      a.write_chunk(OpCode::Ping, b"", false).await,
+      Err(WebSocketError::InvalidValue)
+    ));
+    a.write_chunk(OpCode::Text, b"hel", false).await.unwrap();
+    a.write_chunk(OpCode::Text, b"lo", true).await.unwrap();
+    a.write_chunk(OpCode::Binary, b"!", true).await.unwrap();
+
+    let frame = b.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Text);
+    assert_eq!(frame.payload, b"hello");
+    let frame = b.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Binary);
+    assert_eq!(frame.payload, b"!");
+  }
+
+  #[tokio::test]
+  async fn fragmented_sender() {
+    let (mut a, mut b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+
+    let mut message = a.begin_fragmented_message(OpCode::Binary);
+    message.write_chunk(b"ab").await.unwrap();
+    message.write_chunk(b"c").await.unwrap();
+    message.finish().await.unwrap();
+    a.begin_fragmented_message(OpCode::Text)
+      .finish()
+      .await
+      .unwrap();
+
+    let expected = [
+      (false, OpCode::Binary, &b"ab"[..]),
+      (false, OpCode::Continuation, b"c"),
+      (true, OpCode::Continuation, b""),
+      (true, OpCode::Text, b""),
+    ];
+    for (fin, opcode, payload) in expected {
+      let frame = b.read_frame().await.unwrap();
+      assert_eq!((frame.fin, frame.opcode), (fin, opcode));
+      assert_eq!(frame.payload, payload);
+    }
+  }
+
+  #[tokio::test]
+  async fn read_frame_non_fragmented() {
+    let mut input = Frame::text(b"whole"[..].into()).to_vec();
+    input
+      .extend(Frame::new(false, OpCode::Text, None, b"a"[..].into()).to_vec());
+    input.extend(
+      Frame::new(true, OpCode::Continuation, None, b"b"[..].into()).to_vec(),
+    );
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input),
+      Role::Client,
+    );
+
+    assert_eq!(
+      ws.read_frame_non_fragmented().await.unwrap().payload,
+      b"whole"
+    );
+    for _ in 0..2 {
+      assert!(matches!(
+        ws.read_frame_non_fragmented().await,
+        Err(WebSocketError::UnexpectedContinuation)
+      ));
+    }
+  }
+
+  #[cfg(feature = "codec")]
+  #[tokio::test]
+  async fn into_framed() {
+    use tokio_util::codec::Decoder;
+    use tokio_util::codec::LinesCodec;
+
+    let mut input = Frame::text(b"switch"[..].into()).to_vec();
+    input.extend_from_slice(b"line one\n");
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input),
+      Role::Client,
+    );
+    assert_eq!(ws.read_frame().await.unwrap().payload, b"switch");
+
+    ws.write_frame(Frame::text(b"ok"[..].into())).await.unwrap();
+
+    // The bytes read along with the last frame are kept for the codec.
+    let mut parts = ws.into_framed(LinesCodec::new()).into_parts();
+    let line = parts.codec.decode(&mut parts.read_buf).unwrap();
+    assert_eq!(line.as_deref(), Some("line one"));
+    assert!(parts.write_buf.is_empty());
+  }
+
+  #[tokio::test]
+  async fn write_chunk_in_progress() {
+    let (mut a, b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let mut b = FragmentCollector::new(b);
+
+    a.write_chunk(OpCode::Text, b"hello ", false).await.unwrap();
+    assert!(matches!(
+      a.write_frame(Frame::binary(b"oops"[..].into())).await,
+      Err(WebSocketError::MessageInProgress)
+    ));
+    a.write_frame(Frame::pong(b""[..].into())).await.unwrap();
+    a.write_chunk(OpCode::Text, b"world", true).await.unwrap();
+
+    let frame = b.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Pong);
+    let frame = b.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Text);
+    assert_eq!(frame.payload, b"hello world");
+  }
+
+  #[tokio::test]
+  async fn fragmented_sender_dropped() {
+    let (mut a, _b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let mut message = a.begin_fragmented_message(OpCode::Binary);
+    message.write_chunk(b"part").await.unwrap();
+    drop(message);
+
+    assert!(matches!(
+      a.write_chunk(OpCode::Binary, b"rest", true).await,
+      Err(WebSocketError::MessageInProgress)
+    ));
+    assert!(matches!(
+      a.write_frame(Frame::text(b"next"[..].into())).await,
+      Err(WebSocketError::MessageInProgress)
+    ));
+    a.write_frame(Frame::close(1000, b"")).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn read_all_frames() {
+    let (mut a, mut b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    a.write_frame(Frame::text(b"one"[..].into())).await.unwrap();
+    a.write_frame(Frame::close(1000, b"")).await.unwrap();
+
+    let frames = b.read_all_frames(usize::MAX).await.unwrap();
+    let opcodes: Vec<_> = frames.iter().map(|frame| frame.opcode).collect();
+    assert_eq!(opcodes, [OpCode::Text, OpCode::Close]);
+
+    // The bound stops a peer that never closes.
+    let (mut a, mut b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    for _ in 0..3 {
+      a.write_frame(Frame::text(b"more"[..].into()))
+        .await
+        .unwrap();
+    }
+    assert_eq!(b.read_all_frames(2).await.unwrap().len(), 2);
+  }
+
+  #[tokio::test]
+  async fn send_binary_streaming() {
+    let (mut a, b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let mut b = FragmentCollector::new(b);
+    let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+
+    for frame_size in [1000, 3000] {
+      a.send_binary_streaming(&data[..], frame_size)
+        .await
+        .unwrap();
+      let frame = b.read_frame().await.unwrap();
+      assert_eq!(frame.opcode, OpCode::Binary);
+      assert_eq!(frame.payload, &data[..]);
+    }
+  }
+
+  #[tokio::test]
+  async fn receive_binary_streaming() {
+    let (mut a, mut b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+
+    a.send_binary_streaming(&data[..], 3000).await.unwrap();
+    let mut received = Vec::new();
+    let opcode = b.receive_binary_streaming(&mut received, 10_000).await;
+    assert_eq!(opcode.unwrap(), OpCode::Binary);
+    assert_eq!(received, data);
+
+    a.send_binary_streaming(&data[..], 3000).await.unwrap();
+    assert!(matches!(
+      b.receive_binary_streaming(tokio::io::sink(), 5000).await,
+      Err(WebSocketError::MessageTooLarge)
+    ));
+  }
+
+  #[tokio::test]
+  async fn auto_close_code() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_auto_close_code(Some(CloseCode::Normal));
+
+    client
+      .write_frame(Frame::close(1001, b"away"))
+      .await
+      .unwrap();
+    server.read_frame().await.unwrap();
+    let frame = client.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Close);
+    assert_eq!(frame.payload, b"\x03\xe8");
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_close_handler(|_, _| (CloseCode::Policy, String::new()));
+    server.set_auto_close_code(None);
+
+    client
+      .write_frame(Frame::close(1001, b"away"))
+      .await
+      .unwrap();
+    server.read_frame().await.unwrap();
+    let frame = client.read_frame().await.unwrap();
+    assert_eq!(frame.payload, b"\x03\xe9away");
+  }
+
+  #[tokio::test]
+  async fn reuse_after_clean_close() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    assert!(matches!(
+      server.reuse(),
+      Err(WebSocketError::CloseNotCompleted)
+    ));
+
+    client.write_frame(Frame::close(1000, b"")).await.unwrap();
+    assert_eq!(server.read_frame().await.unwrap().opcode, OpCode::Close);
+    assert_eq!(client.read_frame().await.unwrap().opcode, OpCode::Close);
+    server.reuse().unwrap();
+    client.reuse().unwrap();
+    assert!(!server.is_closed());
+
+    client
+      .write_frame(Frame::text(b"again"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(server.read_frame().await.unwrap().payload, b"again");
+  }
+
+  #[tokio::test]
+  async fn write_frame_scatter() {
+    let frames = || {
+      [
+        Frame::text(b"text"[..].into()),
+        Frame::binary(vec![7; 300].into()),
+        Frame::new(false, OpCode::Text, None, b"frag"[..].into()),
+        Frame::new(true, OpCode::Continuation, None, b"ment"[..].into()),
+        Frame::new(true, OpCode::Ping, None, b"ping"[..].into()),
+        Frame::pong(b"pong"[..].into()),
+        Frame::close(1000, b"done"),
+      ]
+    };
+
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::default(),
+      Role::Server,
+    );
+    for frame in frames() {
+      ws.write_frame_scatter(frame).await.unwrap();
+    }
+    let expected: Vec<u8> = frames().iter().flat_map(Frame::to_vec).collect();
+    assert_eq!(ws.into_inner().written(), expected);
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_auto_pong(false);
+    server.set_auto_close(false);
+    for frame in frames() {
+      client.write_frame_scatter(frame).await.unwrap();
+    }
+    for frame in frames() {
+      let read = server.read_frame().await.unwrap();
+      assert_eq!(read.opcode, frame.opcode);
+      assert_eq!(read.payload, &frame.payload[..]);
+    }
+  }
+
+  #[tokio::test]
+  async fn send_close_and_drain() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server
+      .write_frame(Frame::text(b"last"[..].into()))
+      .await
+      .unwrap();
+    server
+      .send_close_and_drain(CloseCode::Away, "restart")
+      .await
+      .unwrap();
+
+    assert_eq!(client.read_frame().await.unwrap().payload, b"last");
+    let close = client.read_frame().await.unwrap();
+    assert_eq!(close.opcode, OpCode::Close);
+    assert_eq!(close.payload, b"\x03\xe9restart");
+    assert_eq!(server.read_frame().await.unwrap().opcode, OpCode::Close);
+    assert!(server.is_closed());
+  }
+
+  #[tokio::test]
+  async fn read_frame_into() {
+    let (mut server, _client) = test_utils::server_with_frames([
+      Frame::new(false, OpCode::Text, None, b"longer"[..].into()),
+      Frame::new(true, OpCode::Continuation, None, b"ab"[..].into()),
+    ])
+    .await;
+
+    let mut buf = Vec::new();
+    let header = server.read_frame_into(&mut buf).await.unwrap();
+    assert_eq!(
+      header,
+      FrameHeader {
+        fin: false,
+        opcode: OpCode::Text,
+        payload_len: 6,
+      }
+    );
+    assert_eq!(buf, b"longer");
+
+    let capacity = buf.capacity();
+    let header = server.read_frame_into(&mut buf).await.unwrap();
+    assert!(header.fin);
+    assert_eq!(header.opcode, OpCode::Continuation);
+    assert_eq!(buf, b"ab");
+    assert_eq!(buf.capacity(), capacity);
+  }
+
+  #[tokio::test]
+  async fn expect_opcode() {
+    let (mut server, _client) = test_utils::server_with_frames([
+      Frame::binary(b"hello"[..].into()),
+      Frame::text(b"oops"[..].into()),
+    ])
+    .await;
+
+    let frame = server.expect_opcode(OpCode::Binary).await.unwrap();
+    assert_eq!(frame.payload, b"hello");
+    assert!(matches!(
+      server.expect_opcode(OpCode::Binary).await,
+      Err(WebSocketError::UnexpectedOpcode {
+        expected: OpCode::Binary,
+        got: OpCode::Text,
+      })
+    ));
+  }
+
+  #[tokio::test]
+  async fn read_frame_with_metadata() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    client
+      .write_frame(Frame::binary(vec![1; 200].into()))
+      .await
+      .unwrap();
+    server
+      .write_frame(Frame::text(b"hi"[..].into()))
+      .await
+      .unwrap();
+
+    let before = std::time::Instant::now();
+    let (frame, metadata) = server.read_frame_with_metadata().await.unwrap();
+    assert_eq!(frame.payload.len(), 200);
+    assert!(metadata.received_at >= before);
+    assert_eq!(metadata.wire_bytes, 2 + 2 + 4 + 200);
+    assert_eq!(metadata.payload_bytes, 200);
+    assert!(metadata.was_masked);
+
+    let (_, metadata) = client.read_frame_with_metadata().await.unwrap();
+    assert_eq!(metadata.wire_bytes, 4);
+    assert_eq!(metadata.payload_bytes, 2);
+    assert!(!metadata.was_masked);
+  }
+
+  #[tokio::test]
+  async fn save_and_restore_config() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let config = server.save_config();
+    assert!(config.auto_close && config.auto_pong);
+
+    server.set_max_message_size(4);
+    server.set_auto_pong(false);
+    client
+      .write_frame(Frame::binary(b"large"[..].into()))
+      .await
+      .unwrap();
+    assert!(matches!(
+      server.read_frame().await,
+      Err(WebSocketError::FrameTooLarge)
+    ));
+
+    server.restore_config(config);
+    assert_eq!(server.save_config(), config);
+  }
+
+  #[tokio::test]
+  async fn reserved_bits() {
+    let mut frame = Frame::binary(b"x"[..].into());
+    frame.set_rsv1(true);
+    let input = frame.to_vec();
+
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input.clone()),
+      Role::Client,
+    );
+    assert!(matches!(
+      ws.read_frame().await,
+      Err(WebSocketError::ReservedBitsNotZero)
+    ));
+
+    // An extension that does not claim RSV1 does not accept it.
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input.clone()),
+      Role::Client,
+    );
+    ws.set_read_extension(|_: &mut Frame| Ok(()));
+    assert!(matches!(
+      ws.read_frame().await,
+      Err(WebSocketError::ReservedBitsNotZero)
+    ));
+
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(input),
+      Role::Client,
+    );
+    ws.set_read_extension(WithRsv::new(0x40, |frame: &mut Frame| {
+      assert!(frame.rsv1() && !frame.rsv2() && !frame.rsv3());
+      frame.set_rsv1(false);
+      Ok(())
+    }));
+    let frame = ws.read_frame().await.unwrap();
+    assert!(!frame.rsv1());
+    assert_eq!(frame.payload, b"x");
+
+    let mut frame = Frame::binary(b"x"[..].into());
+    frame.set_rsv2(true);
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(frame.to_vec()),
+      Role::Client,
+    );
+    ws.set_read_extension(WithRsv::new(0x40, |_: &mut Frame| Ok(())));
+    assert!(matches!(
+      ws.read_frame().await,
+      Err(WebSocketError::ReservedBitsNotZero)
+    ));
+  }
+
+  #[tokio::test(start_paused = true)]
+  async fn idle_timeout() {
+    use std::time::Duration;
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_idle_timeout(Duration::from_secs(10));
+
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    client
+      .write_frame(Frame::text(b"data"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(server.read_frame().await.unwrap().payload, b"data");
+
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    client
+      .write_frame(Frame::new(true, OpCode::Ping, None, b""[..].into()))
+      .await
+      .unwrap();
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    client
+      .write_frame(Frame::text(b"more"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(server.read_frame().await.unwrap().payload, b"more");
+
+    let start = tokio::time::Instant::now();
+    assert!(matches!(
+      server.read_frame().await,
+      Err(WebSocketError::IdleTimeout)
+    ));
+    assert_eq!(start.elapsed(), Duration::from_secs(10));
+    assert_eq!(client.read_frame().await.unwrap().opcode, OpCode::Pong);
+    let close = client.read_frame().await.unwrap();
+    assert_eq!(close.opcode, OpCode::Close);
+    assert_eq!(close.payload, b"\x03\xe9");
+  }
+
+  #[tokio::test]
+  async fn auto_pong_drop() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_auto_pong_drop(true);
+    client
+      .write_frame(Frame::pong(b"beat"[..].into()))
+      .await
+      .unwrap();
+    client
+      .write_frame(Frame::text(b"data"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(server.read_frame().await.unwrap().payload, b"data");
+
+    // `ping_rtt` still sees its pong, which the client sends while waiting for the next frame.
+    let (rtt, frame) = tokio::join!(
+      async {
+        let rtt = server
+          .ping_rtt(b"rtt".to_vec(), std::time::Duration::from_secs(5))
+          .await;
+        server
+          .write_frame(Frame::text(b"done"[..].into()))
+          .await
+          .unwrap();
+        rtt
+      },
+      client.read_frame()
+    );
+    rtt.unwrap();
+    assert_eq!(frame.unwrap().payload, b"done");
+    server.set_auto_pong_drop(false);
+    client
+      .write_frame(Frame::pong(b"beat"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(server.read_frame().await.unwrap().opcode, OpCode::Pong);
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    server.set_auto_pong_drop(true);
+    let mut server = FragmentCollector::new(server);
+    client
+      .write_frame(Frame::pong(b"beat"[..].into()))
+      .await
+      .unwrap();
+    client
+      .write_frame(Frame::text(b"data"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(server.read_frame().await.unwrap().payload, b"data");
+  }
+
+  #[tokio::test]
+  async fn send_ping() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    assert!(matches!(
+      client.send_ping(&[0; 126]).await,
+      Err(WebSocketError::PingFrameTooLarge)
+    ));
+    client.send_ping(&[1; 125]).await.unwrap();
+    server.set_auto_pong(false);
+    let ping = server.read_frame().await.unwrap();
+    assert_eq!(ping.opcode, OpCode::Ping);
+    assert_eq!(ping.payload, &[1; 125]);
+  }
+
+  #[tokio::test]
+  async fn write_frame_zero_copy() {
+    let payload = Bytes::from(vec![5; 2000]);
+    let header = FrameHeader {
+      fin: true,
+      opcode: OpCode::Binary,
+      payload_len: payload.len(),
+    };
+
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::default(),
+      Role::Server,
+    );
+    ws.write_frame_zero_copy(header, &payload).await.unwrap();
+    assert!(matches!(
+      ws.write_frame_zero_copy(header, &payload.slice(1..)).await,
+      Err(WebSocketError::InvalidValue)
+    ));
+    assert_eq!(
+      ws.into_inner().written(),
+      Frame::binary(payload.to_vec().into()).to_vec()
+    );
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    client
+      .write_frame_zero_copy(header, &payload)
+      .await
+      .unwrap();
+    assert_eq!(server.read_frame().await.unwrap().payload, &payload[..]);
+    assert_eq!(payload, vec![5; 2000]);
+  }
+
+  #[tokio::test]
+  async fn read_frame_until() {
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    for payload in [&b"a"[..], b"b", b"ack", b"c", b"d", b"e"] {
+      client
+        .write_frame(Frame::binary(payload.into()))
+        .await
+        .unwrap();
+    }
+    client.write_frame(Frame::close(1000, b"")).await.unwrap();
+
+    let is_ack = |frame: &Frame| frame.payload == b"ack";
+    let frame = server.read_frame_until(is_ack, 2).await.unwrap();
+    assert_eq!(frame.payload, b"ack");
+    assert!(matches!(
+      server.read_frame_until(is_ack, 1).await,
+      Err(WebSocketError::NotFound)
+    ));
+    assert_eq!(server.read_frame().await.unwrap().payload, b"e");
+    assert!(matches!(
+      server.read_frame_until(is_ack, 5).await,
+      Err(WebSocketError::ConnectionClosed)
+    ));
+  }
+
+  #[tokio::test]
+  async fn write_batch() {
+    let payloads = (0..1500u16)
+      .map(|i| i.to_string().into_bytes())
+      .collect::<Vec<_>>();
+    let mut frames = payloads
+      .iter()
+      .map(|payload| (&payload[..], OpCode::Text))
+      .collect::<Vec<_>>();
+    frames.push((&[], OpCode::Binary));
+
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::default(),
+      Role::Server,
+    );
+    ws.write_batch(&frames).await.unwrap();
+    let expected: Vec<u8> = frames
+      .iter()
+      .flat_map(|&(payload, opcode)| {
+        Frame::new(true, opcode, None, payload.into()).to_vec()
+      })
+      .collect();
+    assert_eq!(ws.into_inner().written(), expected);
+
+    let (mut server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let batch = [(&b"one"[..], OpCode::Text), (b"two", OpCode::Binary)];
+    client.write_batch(&batch).await.unwrap();
+
+    // Invalid batches are rejected before anything is written or the close state changes.
+    let close = 1000u16.to_be_bytes();
+    assert!(matches!(
+      client
+        .write_batch(&[(&close[..], OpCode::Close), (b"late", OpCode::Text)])
+        .await,
+      Err(WebSocketError::ConnectionClosed)
+    ));
+    assert!(!client.write_half.closed);
+    client
+      .write_chunk(OpCode::Text, b"chunk", false)
+      .await
+      .unwrap();
+    assert!(matches!(
+      client
+        .write_batch(&[(b"ping", OpCode::Ping), (b"no", OpCode::Text)])
+        .await,
+      Err(WebSocketError::MessageInProgress)
+    ));
+    client.write_chunk(OpCode::Text, b"", true).await.unwrap();
+    for (payload, opcode) in batch {
+      let frame = server.read_frame().await.unwrap();
+      assert_eq!(frame.opcode, opcode);
+      assert_eq!(frame.payload, payload);
+    }
+  }
+
+  #[tokio::test]
+  async fn on_ping_pong_too_large() {
+    let mut ws = WebSocket::after_handshake(
+      crate::test_utils::MockStream::with_data(
+        Frame::new(true, OpCode::Ping, None, b"ping"[..].into()).to_vec(),
+      ),
+      Role::Client,
+    );
+    ws.set_on_ping(|_| Some(vec![0; 126]));
+    assert!(matches!(
+      ws.read_frame().await,
+      Err(WebSocketError::PongFrameTooLarge)
+    ));
+    assert!(ws.into_inner().written().is_empty());
+  }
 }
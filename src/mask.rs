@@ -128,6 +128,28 @@ pub fn unmask(payload: &mut [u8], mask: [u8; 4]) {
   unmask_fallback(payload, mask)
 }
 
+/// Applies a mask to a payload that arrives in several chunks.
+///
+/// Keeps track of the offset into the 4-byte mask key, so each chunk can be unmasked in place as
+/// soon as it is read, regardless of where the previous chunk ended.
+#[derive(Clone, Copy, Debug)]
+pub struct MaskingState([u8; 4], usize);
+
+impl MaskingState {
+  pub fn new(mask: [u8; 4]) -> Self {
+    Self(mask, 0)
+  }
+
+  /// Masks or unmasks `chunk` in place, continuing from the end of the previous chunk.
+  #[inline]
+  pub fn apply(&mut self, chunk: &mut [u8]) {
+    let mut mask = self.0;
+    mask.rotate_left(self.1 & 3);
+    unmask_fallback(chunk, mask);
+    self.1 = self.1.wrapping_add(chunk.len());
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -169,4 +191,20 @@ mod tests {
       assert_eq!(payload, expected);
     }
   }
+
+  #[test]
+  fn masking_state_chunks() {
+    let mask = rand::random::<[u8; 4]>();
+    let mut expected = vec![0u8; 100];
+    unmask(&mut expected, mask);
+
+    for split in [1, 3, 4, 7, 50] {
+      let mut payload = vec![0u8; 100];
+      let mut state = MaskingState::new(mask);
+      for chunk in payload.chunks_mut(split) {
+        state.apply(chunk);
+      }
+      assert_eq!(payload, expected);
+    }
+  }
 }
@@ -0,0 +1,216 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+use crate::Frame;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+/// A protocol adapter that inspects or rewrites frames, e.g. for logging, authentication or
+/// custom compression. Install it with [`WebSocketMiddleware::new`].
+///
+/// Returning an error aborts the read or write.
+pub trait Middleware {
+  /// Called on every frame returned by the wrapped connection.
+  fn on_read(&mut self, frame: &mut Frame) -> Result<(), WebSocketError>;
+
+  /// Called on every frame before it is passed to the wrapped connection.
+  fn on_write(&mut self, frame: &mut Frame) -> Result<(), WebSocketError>;
+}
+
+/// A connection that frames can be read from and written to.
+///
+/// Implemented by [`WebSocket`] and [`WebSocketMiddleware`], so that middlewares can be stacked.
+pub trait FrameStream {
+  fn read_frame(
+    &mut self,
+  ) -> impl Future<Output = Result<Frame<'static>, WebSocketError>>;
+
+  fn write_frame<'a>(
+    &'a mut self,
+    frame: Frame<'a>,
+  ) -> impl Future<Output = Result<(), WebSocketError>>;
+}
+
+impl<S> FrameStream for WebSocket<S>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  fn read_frame(
+    &mut self,
+  ) -> impl Future<Output = Result<Frame<'static>, WebSocketError>> {
+    WebSocket::read_frame(self)
+  }
+
+  fn write_frame<'a>(
+    &'a mut self,
+    frame: Frame<'a>,
+  ) -> impl Future<Output = Result<(), WebSocketError>> {
+    WebSocket::write_frame(self, frame)
+  }
+}
+
+/// Wraps a connection and passes every frame read from or written to it through a [`Middleware`].
+///
+/// The wrapped connection can be a [`WebSocket`] or another `WebSocketMiddleware`. The innermost
+/// middleware is the closest to the wire: it sees received frames first and outgoing frames last.
+/// Middlewares only see the frames returned by [`WebSocket::read_frame`], so pings and close frames
+/// that are answered automatically do not pass through them.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{Frame, Middleware, WebSocket, WebSocketError, WebSocketMiddleware};
+/// use tokio::net::TcpStream;
+///
+/// struct Logger;
+///
+/// impl Middleware for Logger {
+///   fn on_read(&mut self, frame: &mut Frame) -> Result<(), WebSocketError> {
+///     println!("<- {:?}", frame.opcode);
+///     Ok(())
+///   }
+///
+///   fn on_write(&mut self, frame: &mut Frame) -> Result<(), WebSocketError> {
+///     println!("-> {:?}", frame.opcode);
+///     Ok(())
+///   }
+/// }
+///
+/// async fn echo(ws: WebSocket<TcpStream>) -> Result<(), WebSocketError> {
+///   let mut ws = WebSocketMiddleware::new(ws, Logger);
+///   let frame = ws.read_frame().await?;
+///   ws.write_frame(frame).await
+/// }
+/// ```
+pub struct WebSocketMiddleware<S, M> {
+  inner: S,
+  middleware: M,
+}
+
+impl<S, M> WebSocketMiddleware<S, M> {
+  pub fn new(inner: S, middleware: M) -> Self {
+    Self { inner, middleware }
+  }
+
+  /// Returns a reference to the wrapped connection.
+  pub fn get_ref(&self) -> &S {
+    &self.inner
+  }
+
+  /// Returns a mutable reference to the wrapped connection. Frames read or written through it bypass
+  /// this middleware.
+  pub fn get_mut(&mut self) -> &mut S {
+    &mut self.inner
+  }
+
+  /// Returns a mutable reference to the middleware.
+  pub fn middleware_mut(&mut self) -> &mut M {
+    &mut self.middleware
+  }
+
+  /// Consumes the `WebSocketMiddleware` and returns the wrapped connection and the middleware.
+  pub fn into_inner(self) -> (S, M) {
+    (self.inner, self.middleware)
+  }
+
+  /// Reads a frame from the wrapped connection and passes it through the middleware.
+  pub async fn read_frame(&mut self) -> Result<Frame<'static>, WebSocketError>
+  where
+    S: FrameStream,
+    M: Middleware,
+  {
+    let mut frame = self.inner.read_frame().await?;
+    self.middleware.on_read(&mut frame)?;
+    Ok(frame)
+  }
+
+  /// Passes a frame through the middleware and writes it to the wrapped connection.
+  pub async fn write_frame(
+    &mut self,
+    mut frame: Frame<'_>,
+  ) -> Result<(), WebSocketError>
+  where
+    S: FrameStream,
+    M: Middleware,
+  {
+    self.middleware.on_write(&mut frame)?;
+    self.inner.write_frame(frame).await
+  }
+}
+
+impl<S, M> FrameStream for WebSocketMiddleware<S, M>
+where
+  S: FrameStream,
+  M: Middleware,
+{
+  fn read_frame(
+    &mut self,
+  ) -> impl Future<Output = Result<Frame<'static>, WebSocketError>> {
+    WebSocketMiddleware::read_frame(self)
+  }
+
+  fn write_frame<'a>(
+    &'a mut self,
+    frame: Frame<'a>,
+  ) -> impl Future<Output = Result<(), WebSocketError>> {
+    WebSocketMiddleware::write_frame(self, frame)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Role;
+
+  #[tokio::test]
+  async fn stacked_middleware() {
+    struct Tag(u8);
+
+    impl Middleware for Tag {
+      fn on_read(&mut self, frame: &mut Frame) -> Result<(), WebSocketError> {
+        if frame.payload.last() != Some(&self.0) {
+          return Err(WebSocketError::InvalidValue);
+        }
+        let len = frame.payload.len();
+        frame.truncate_payload(len - 1);
+        Ok(())
+      }
+
+      fn on_write(&mut self, frame: &mut Frame) -> Result<(), WebSocketError> {
+        frame.extend_payload(&[self.0]);
+        Ok(())
+      }
+    }
+
+    let (a, b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let mut a =
+      WebSocketMiddleware::new(WebSocketMiddleware::new(a, Tag(1)), Tag(2));
+    let mut b =
+      WebSocketMiddleware::new(WebSocketMiddleware::new(b, Tag(1)), Tag(2));
+
+    a.write_frame(Frame::text(b"hi"[..].into())).await.unwrap();
+    // The innermost middleware is applied last on write.
+    let frame = b.get_mut().get_mut().read_frame().await.unwrap();
+    assert_eq!(frame.payload, b"hi\x02\x01");
+
+    b.write_frame(Frame::text(b"hey"[..].into())).await.unwrap();
+    let frame = a.read_frame().await.unwrap();
+    assert_eq!(frame.payload, b"hey");
+  }
+}
@@ -0,0 +1,164 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bytes::Bytes;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+use crate::FragmentCollector;
+use crate::Frame;
+use crate::OpCode;
+use crate::Payload;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+/// Identifies a logical channel of a [`MultiplexedWebSocket`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChannelId(pub u16);
+
+/// Multiplexes logical channels over a single WebSocket connection.
+///
+/// Every message is a binary frame whose payload starts with the channel ID as a big-endian `u16`,
+/// followed by the data. Messages are delivered in the order they were sent across all channels.
+/// Fragmented messages are reassembled before they are routed.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{MultiplexedWebSocket, WebSocket, WebSocketError};
+/// use tokio::net::TcpStream;
+///
+/// async fn handle(ws: WebSocket<TcpStream>) -> Result<(), WebSocketError> {
+///   let mut ws = MultiplexedWebSocket::new(ws);
+///   let control = ws.open_channel();
+///   let data = ws.open_channel();
+///   ws.send_on(control, b"hello").await?;
+///
+///   loop {
+///     let (channel, payload) = ws.recv().await?;
+///     if channel == data {
+///       ws.send_on(data, &payload).await?;
+///     }
+///   }
+/// }
+/// ```
+pub struct MultiplexedWebSocket<S> {
+  ws: FragmentCollector<S>,
+  next_channel: u16,
+  buffer: Vec<u8>,
+}
+
+impl<S> MultiplexedWebSocket<S> {
+  pub fn new(ws: WebSocket<S>) -> Self
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    Self {
+      ws: FragmentCollector::new(ws),
+      next_channel: 0,
+      buffer: Vec::new(),
+    }
+  }
+
+  /// Returns a new channel ID.
+  ///
+  /// IDs are handed out in increasing order, starting at 0, and are not negotiated with the peer:
+  /// both ends must agree on their meaning, e.g. by opening channels in the same order. After
+  /// 65536 channels, IDs are reused.
+  pub fn open_channel(&mut self) -> ChannelId {
+    let id = ChannelId(self.next_channel);
+    self.next_channel = self.next_channel.wrapping_add(1);
+    id
+  }
+
+  /// Sends `data` on channel `id`.
+  pub async fn send_on(
+    &mut self,
+    id: ChannelId,
+    data: &[u8],
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self.buffer.clear();
+    self.buffer.extend_from_slice(&id.0.to_be_bytes());
+    self.buffer.extend_from_slice(data);
+    self
+      .ws
+      .write_frame(Frame::binary(Payload::Borrowed(&self.buffer)))
+      .await
+  }
+
+  /// Receives the next message and the channel it was sent on.
+  ///
+  /// Fails with [`WebSocketError::ConnectionClosed`] once a close frame is received and with
+  /// [`WebSocketError::InvalidValue`] if a message is too short to hold a channel ID.
+  pub async fn recv(&mut self) -> Result<(ChannelId, Bytes), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    loop {
+      let frame = self.ws.read_frame().await?;
+      match frame.opcode {
+        OpCode::Close => return Err(WebSocketError::ConnectionClosed),
+        OpCode::Text | OpCode::Binary => {}
+        _ => continue,
+      }
+      if frame.payload.len() < 2 {
+        return Err(WebSocketError::InvalidValue);
+      }
+      let mut payload = match frame.payload {
+        Payload::Bytes(payload) => payload.freeze(),
+        payload => Bytes::from(Vec::from(payload)),
+      };
+      let id = ChannelId(u16::from_be_bytes([payload[0], payload[1]]));
+      return Ok((id, payload.split_off(2)));
+    }
+  }
+
+  /// Consumes the `MultiplexedWebSocket` and returns the underlying stream.
+  pub fn into_inner(self) -> S {
+    self.ws.into_inner()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Role;
+
+  #[tokio::test]
+  async fn multiplexed() {
+    let (a, b) = WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let mut a = MultiplexedWebSocket::new(a);
+    let mut b = MultiplexedWebSocket::new(b);
+    let first = a.open_channel();
+    let second = a.open_channel();
+    assert_eq!((first, second), (ChannelId(0), ChannelId(1)));
+
+    a.send_on(second, b"two").await.unwrap();
+    a.send_on(first, b"one").await.unwrap();
+    assert_eq!(
+      b.recv().await.unwrap(),
+      (second, bytes::Bytes::from_static(b"two"))
+    );
+    assert_eq!(
+      b.recv().await.unwrap(),
+      (first, bytes::Bytes::from_static(b"one"))
+    );
+
+    b.send_on(first, b"").await.unwrap();
+    assert_eq!(a.recv().await.unwrap(), (first, bytes::Bytes::new()));
+  }
+}
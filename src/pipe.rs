@@ -0,0 +1,147 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::poll_fn;
+use std::future::Future;
+use std::pin::pin;
+use std::task::Poll;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+use crate::close::encode_close_payload;
+use crate::CloseCode;
+use crate::Frame;
+use crate::OpCode;
+use crate::WebSocket;
+use crate::WebSocketError;
+use crate::WebSocketEvent;
+
+/// Relays frames between two connections in both directions until one of them is closed, e.g. in a
+/// WebSocket proxy.
+///
+/// Data frames are forwarded as they arrive, including fragments; pings and pongs are handled by
+/// each connection on its own and are not forwarded. When either peer sends a close frame, it is
+/// forwarded to the other connection, the relay waits for that connection to answer it so both
+/// closing handshakes complete, and the code and reason are returned. A close frame without a
+/// status code is returned as [`CloseCode::Status`]. The first error on either connection stops the
+/// relay.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{pipe, WebSocket};
+/// use tokio::net::TcpStream;
+///
+/// async fn proxy(client: WebSocket<TcpStream>, upstream: WebSocket<TcpStream>) {
+///   match pipe(client, upstream).await {
+///     Ok((code, reason)) => println!("closed: {:?} {}", code, reason),
+///     Err(e) => eprintln!("relay failed: {}", e),
+///   }
+/// }
+/// ```
+pub async fn pipe<S1, S2>(
+  mut ws1: WebSocket<S1>,
+  mut ws2: WebSocket<S2>,
+) -> Result<(CloseCode, String), WebSocketError>
+where
+  S1: AsyncRead + AsyncWrite + Unpin,
+  S2: AsyncRead + AsyncWrite + Unpin,
+{
+  loop {
+    // Reads are cancel safe, so the read that did not complete can be dropped and started again.
+    let (from_first, frame) = {
+      let mut read1 = pin!(ws1.read_frame());
+      let mut read2 = pin!(ws2.read_frame());
+      poll_fn(|cx| {
+        if let Poll::Ready(res) = read1.as_mut().poll(cx) {
+          return Poll::Ready((true, res));
+        }
+        if let Poll::Ready(res) = read2.as_mut().poll(cx) {
+          return Poll::Ready((false, res));
+        }
+        Poll::Pending
+      })
+      .await
+    };
+    let frame = frame?;
+
+    let frame = match frame.opcode {
+      OpCode::Text | OpCode::Binary | OpCode::Continuation => frame,
+      OpCode::Close => {
+        let WebSocketEvent::Close(code, reason) =
+          WebSocketEvent::from_frame(frame)?
+        else {
+          unreachable!()
+        };
+        let close =
+          Frame::close_raw(encode_close_payload(code, &reason).into());
+        if from_first {
+          ws2.write_frame(close).await?;
+          while ws2.read_frame().await?.opcode != OpCode::Close {}
+        } else {
+          ws1.write_frame(close).await?;
+          while ws1.read_frame().await?.opcode != OpCode::Close {}
+        }
+        return Ok((code, reason));
+      }
+      _ => continue,
+    };
+    // Masking is applied per connection, so a frame read with its mask is sent without it.
+    let frame = Frame::new(frame.fin, frame.opcode, None, frame.payload);
+    if from_first {
+      ws2.write_frame(frame).await?;
+    } else {
+      ws1.write_frame(frame).await?;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Role;
+
+  #[tokio::test]
+  async fn pipe_relays_until_close() {
+    let (mut client, proxy_client) =
+      WebSocket::in_memory_pair(Role::Client, Role::Server);
+    let (proxy_upstream, mut upstream) =
+      WebSocket::in_memory_pair(Role::Client, Role::Server);
+    let relay = tokio::spawn(pipe(proxy_client, proxy_upstream));
+
+    client
+      .write_frame(Frame::text(b"up"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(upstream.read_frame().await.unwrap().payload, b"up");
+    upstream
+      .write_frame(Frame::binary(b"down"[..].into()))
+      .await
+      .unwrap();
+    assert_eq!(client.read_frame().await.unwrap().payload, b"down");
+
+    upstream
+      .write_frame(Frame::close(1001, b"bye"))
+      .await
+      .unwrap();
+    let close = client.read_frame().await.unwrap();
+    assert_eq!(close.opcode, OpCode::Close);
+    assert_eq!(close.payload, b"\x03\xe9bye");
+    assert_eq!(
+      relay.await.unwrap().unwrap(),
+      (CloseCode::Away, "bye".to_string())
+    );
+  }
+}
@@ -0,0 +1,44 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// A leaky bucket: every transferred byte pushes back the time at which the next transfer may start.
+pub(crate) struct RateLimiter {
+  bytes_per_sec: u64,
+  available_at: Instant,
+}
+
+impl RateLimiter {
+  /// Returns `None` if `bytes_per_sec` is zero, i.e. unlimited.
+  pub(crate) fn new(bytes_per_sec: u64) -> Option<Self> {
+    (bytes_per_sec > 0).then(|| Self {
+      bytes_per_sec,
+      available_at: Instant::now(),
+    })
+  }
+
+  /// Waits until the bytes recorded so far have drained at the configured rate.
+  pub(crate) async fn wait(&self) {
+    tokio::time::sleep_until(self.available_at).await;
+  }
+
+  pub(crate) fn record(&mut self, bytes: usize) {
+    let cost =
+      Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+    self.available_at = self.available_at.max(Instant::now()) + cost;
+  }
+}
@@ -0,0 +1,153 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::sync::mpsc;
+
+use crate::Frame;
+use crate::OpCode;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+type Extractor = Box<dyn FnMut(&Frame) -> Option<u8> + Send>;
+
+/// Reads frames from a `WebSocket` and forwards them to consumers by routing key.
+///
+/// By default the routing key is the first byte of the payload. Text, binary and continuation
+/// frames are routed; frames without a matching consumer are dropped. The key of a fragmented
+/// message is taken from its first frame, and its continuation frames go to the same consumer.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{FrameRouter, WebSocket};
+/// use tokio::net::TcpStream;
+/// use tokio::sync::mpsc;
+///
+/// async fn route(ws: WebSocket<TcpStream>) {
+///   let (chat_tx, mut chat_rx) = mpsc::channel(16);
+///   let mut router = FrameRouter::new(ws);
+///   router.register(1, chat_tx);
+///
+///   tokio::spawn(router.run());
+///   while let Some(frame) = chat_rx.recv().await {
+///     // Handle messages for channel 1
+///   }
+/// }
+/// ```
+pub struct FrameRouter<S> {
+  ws: WebSocket<S>,
+  routes: HashMap<u8, mpsc::Sender<Frame<'static>>>,
+  extractor: Extractor,
+}
+
+impl<S> FrameRouter<S> {
+  /// Creates a new `FrameRouter` that routes frames by the first byte of their payload.
+  pub fn new(ws: WebSocket<S>) -> Self {
+    Self::with_extractor(ws, |frame| frame.payload.first().copied())
+  }
+
+  /// Creates a new `FrameRouter` that routes frames by the key returned from `extractor`.
+  /// Frames for which `extractor` returns `None` are dropped.
+  pub fn with_extractor<F>(ws: WebSocket<S>, extractor: F) -> Self
+  where
+    F: FnMut(&Frame) -> Option<u8> + Send + 'static,
+  {
+    Self {
+      ws,
+      routes: HashMap::new(),
+      extractor: Box::new(extractor),
+    }
+  }
+
+  /// Registers the consumer for frames with routing key `id`, replacing any previous one.
+  pub fn register(&mut self, id: u8, tx: mpsc::Sender<Frame<'static>>) {
+    self.routes.insert(id, tx);
+  }
+
+  /// Reads and routes frames until a close frame is received.
+  ///
+  /// Consumers whose receiver has been dropped are unregistered.
+  pub async fn run(mut self) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    // The key of the fragmented message in progress, if any.
+    let mut message_key = None;
+    loop {
+      let frame = self.ws.read_frame().await?;
+      let key = match frame.opcode {
+        OpCode::Close => return Ok(()),
+        OpCode::Text | OpCode::Binary => (self.extractor)(&frame),
+        OpCode::Continuation => message_key.flatten(),
+        _ => continue,
+      };
+      message_key = (!frame.fin).then_some(key);
+
+      let Some(id) = key else {
+        continue;
+      };
+      if let Some(tx) = self.routes.get(&id) {
+        if tx.send(frame).await.is_err() {
+          self.routes.remove(&id);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::Role;
+
+  #[tokio::test]
+  async fn fragments_follow_first_frame() {
+    let (server, mut client) =
+      WebSocket::in_memory_pair(Role::Server, Role::Client);
+    let (one_tx, mut one_rx) = mpsc::channel(16);
+    let (two_tx, mut two_rx) = mpsc::channel(16);
+    let mut router = FrameRouter::new(server);
+    router.register(1, one_tx);
+    router.register(2, two_tx);
+    let run = tokio::spawn(router.run());
+
+    client
+      .write_frame(Frame::new(false, OpCode::Binary, None, vec![1, 0].into()))
+      .await
+      .unwrap();
+    // Starts with the key of the other consumer.
+    client
+      .write_frame(Frame::new(true, OpCode::Continuation, None, vec![2].into()))
+      .await
+      .unwrap();
+    client
+      .write_frame(Frame::binary(vec![2, 0].into()))
+      .await
+      .unwrap();
+    client.write_frame(Frame::close(1000, b"")).await.unwrap();
+    run.await.unwrap().unwrap();
+
+    assert_eq!(one_rx.recv().await.unwrap().payload, &[1, 0]);
+    let frame = one_rx.recv().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Continuation);
+    assert_eq!(frame.payload, &[2]);
+    assert!(one_rx.recv().await.is_none());
+    assert_eq!(two_rx.recv().await.unwrap().payload, &[2, 0]);
+    assert!(two_rx.recv().await.is_none());
+  }
+}
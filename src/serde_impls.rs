@@ -0,0 +1,164 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `serde` support for frames, opcodes and close codes.
+//!
+//! Opcodes and close codes are encoded as their numeric value. Frames are encoded as a struct with
+//! the payload in base64 and its decoded length, which is checked on deserialization, and the
+//! reserved bits in their position in the first header byte.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::Error as _;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::CloseCode;
+use crate::Frame;
+use crate::OpCode;
+
+impl Serialize for OpCode {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u8(*self as u8)
+  }
+}
+
+impl<'de> Deserialize<'de> for OpCode {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    let value = u8::deserialize(deserializer)?;
+    OpCode::try_from(value)
+      .map_err(|_| D::Error::custom(format!("invalid opcode: {value:#x}")))
+  }
+}
+
+impl Serialize for CloseCode {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u16((*self).into())
+  }
+}
+
+impl<'de> Deserialize<'de> for CloseCode {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    u16::deserialize(deserializer).map(CloseCode::from)
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameRepr {
+  fin: bool,
+  /// Absent in frames serialized before the reserved bits were recorded.
+  #[serde(default)]
+  rsv: u8,
+  opcode: OpCode,
+  mask: Option<[u8; 4]>,
+  len: usize,
+  payload: String,
+}
+
+impl Serialize for Frame<'_> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    FrameRepr {
+      fin: self.fin,
+      rsv: self.rsv,
+      opcode: self.opcode,
+      mask: self.mask,
+      len: self.payload.len(),
+      payload: STANDARD.encode(&self.payload[..]),
+    }
+    .serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Frame<'static> {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    let repr = FrameRepr::deserialize(deserializer)?;
+    let payload = STANDARD.decode(&repr.payload).map_err(D::Error::custom)?;
+    if payload.len() != repr.len {
+      return Err(D::Error::custom(format!(
+        "payload length mismatch: expected {}, got {}",
+        repr.len,
+        payload.len()
+      )));
+    }
+    if repr.rsv & !0b0111_0000 != 0 {
+      return Err(D::Error::custom(format!(
+        "invalid reserved bits: {:#x}",
+        repr.rsv
+      )));
+    }
+    Ok(
+      Frame::new(repr.fin, repr.opcode, repr.mask, payload.into())
+        .with_rsv(repr.rsv),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn frame_round_trip() {
+    let frame = Frame::new(
+      false,
+      OpCode::Binary,
+      Some([1, 2, 3, 4]),
+      vec![0, 255, 7].into(),
+    );
+    let json = serde_json::to_string(&frame).unwrap();
+    let decoded: Frame = serde_json::from_str(&json).unwrap();
+    assert!(!decoded.fin);
+    assert!(!decoded.rsv1());
+    assert_eq!(decoded.opcode, OpCode::Binary);
+    assert_eq!(decoded.mask, Some([1, 2, 3, 4]));
+    assert_eq!(decoded.payload, &[0, 255, 7]);
+
+    let code: CloseCode =
+      serde_json::from_str(&serde_json::to_string(&CloseCode::Away).unwrap())
+        .unwrap();
+    assert_eq!(code, CloseCode::Away);
+
+    let mut frame = Frame::binary(b"x"[..].into());
+    frame.set_rsv1(true);
+    frame.set_rsv3(true);
+    let decoded: Frame =
+      serde_json::from_str(&serde_json::to_string(&frame).unwrap()).unwrap();
+    assert!(decoded.rsv1() && !decoded.rsv2() && decoded.rsv3());
+  }
+
+  #[test]
+  fn frame_validation() {
+    let invalid_opcode =
+      r#"{"fin":true,"opcode":3,"mask":null,"len":0,"payload":""}"#;
+    assert!(serde_json::from_str::<Frame>(invalid_opcode).is_err());
+
+    let valid =
+      r#"{"fin":true,"opcode":1,"mask":null,"len":2,"payload":"aGk="}"#;
+    assert!(serde_json::from_str::<Frame>(valid).is_ok());
+    let wrong_len =
+      r#"{"fin":true,"opcode":1,"mask":null,"len":3,"payload":"aGk="}"#;
+    assert!(serde_json::from_str::<Frame>(wrong_len).is_err());
+    let invalid_rsv =
+      r#"{"fin":true,"rsv":128,"opcode":1,"mask":null,"len":0,"payload":""}"#;
+    assert!(serde_json::from_str::<Frame>(invalid_rsv).is_err());
+  }
+}
@@ -0,0 +1,165 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+
+use futures_sink::Sink;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+use crate::Frame;
+use crate::WebSocketError;
+use crate::WriteHalf;
+
+/// A cloneable handle to a stream shared between a [`WebSocketRead`](crate::WebSocketRead) and a [`WebSocketSink`].
+///
+/// The stream is only locked for the duration of a single poll, so a read task and a write task never
+/// wait on each other across `.await` points. This does not require the stream to support splitting.
+pub struct SharedStream<S>(Arc<Mutex<S>>);
+
+impl<S> SharedStream<S> {
+  pub fn new(stream: S) -> Self {
+    Self(Arc::new(Mutex::new(stream)))
+  }
+}
+
+impl<S> Clone for SharedStream<S> {
+  fn clone(&self) -> Self {
+    Self(self.0.clone())
+  }
+}
+
+impl<S> AsyncRead for SharedStream<S>
+where
+  S: AsyncRead + Unpin,
+{
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let mut stream = self.0.lock().unwrap();
+    Pin::new(&mut *stream).poll_read(cx, buf)
+  }
+}
+
+impl<S> AsyncWrite for SharedStream<S>
+where
+  S: AsyncWrite + Unpin,
+{
+  fn poll_write(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    let mut stream = self.0.lock().unwrap();
+    Pin::new(&mut *stream).poll_write(cx, buf)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let mut stream = self.0.lock().unwrap();
+    Pin::new(&mut *stream).poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let mut stream = self.0.lock().unwrap();
+    Pin::new(&mut *stream).poll_shutdown(cx)
+  }
+}
+
+/// The write half of a WebSocket connection as a [`Sink`] of frames.
+///
+/// Each frame is serialized in `start_send` and written out by `poll_ready`, `poll_flush`
+/// or `poll_close`. Only one frame is buffered at a time.
+///
+/// Use [`WebSocket::split_sink`](crate::WebSocket::split_sink) to create one.
+pub struct WebSocketSink<S> {
+  stream: S,
+  write_half: WriteHalf,
+}
+
+impl<S> WebSocketSink<S> {
+  pub(crate) fn new(stream: S, write_half: WriteHalf) -> Self {
+    Self { stream, write_half }
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.write_half.closed
+  }
+
+  fn poll_write_pending(
+    &mut self,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<(), WebSocketError>>
+  where
+    S: AsyncWrite + Unpin,
+  {
+    self.write_half.poll_flush_pending(&mut self.stream, cx)
+  }
+}
+
+impl<'f, S> Sink<Frame<'f>> for WebSocketSink<S>
+where
+  S: AsyncWrite + Unpin,
+{
+  type Error = WebSocketError;
+
+  fn poll_ready(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<(), Self::Error>> {
+    self.get_mut().poll_write_pending(cx)
+  }
+
+  fn start_send(
+    self: Pin<&mut Self>,
+    frame: Frame<'f>,
+  ) -> Result<(), Self::Error> {
+    self.get_mut().write_half.queue_frame(frame)
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<(), Self::Error>> {
+    let this = self.get_mut();
+    ready!(this.poll_write_pending(cx))?;
+    Pin::new(&mut this.stream)
+      .poll_flush(cx)
+      .map_err(Into::into)
+  }
+
+  fn poll_close(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Result<(), Self::Error>> {
+    let this = self.get_mut();
+    ready!(this.poll_write_pending(cx))?;
+    Pin::new(&mut this.stream)
+      .poll_shutdown(cx)
+      .map_err(Into::into)
+  }
+}
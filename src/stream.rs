@@ -0,0 +1,97 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::future::Future;
+use std::pin::pin;
+use std::pin::Pin;
+use std::task::ready;
+use std::task::Context;
+use std::task::Poll;
+
+use futures_core::Stream;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+
+use crate::Frame;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+/// Yields frames like [`WebSocket::read_frame`], ending after the close frame.
+///
+/// Reading is cancel safe, so each poll starts a new read when the previous one did not complete.
+/// Replies that a previous read left unwritten are flushed before reading on.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::{OpCode, WebSocket};
+/// use futures_util::StreamExt;
+/// use tokio::net::TcpStream;
+///
+/// async fn count_messages(mut ws: WebSocket<TcpStream>) -> usize {
+///   let mut count = 0;
+///   while let Some(Ok(frame)) = ws.next().await {
+///     if frame.opcode != OpCode::Close {
+///       count += 1;
+///     }
+///   }
+///   count
+/// }
+/// ```
+impl<S> Stream for WebSocket<S>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  type Item = Result<Frame<'static>, WebSocketError>;
+
+  fn poll_next(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<Option<Self::Item>> {
+    let this = self.get_mut();
+    let flush = this.write_half.flush_pending(&mut this.stream);
+    if let Err(e) = ready!(pin!(flush).poll(cx)) {
+      return Poll::Ready(Some(Err(e)));
+    }
+    if this.read_half.close_received {
+      return Poll::Ready(None);
+    }
+
+    let frame = ready!(pin!(this.read_frame()).poll(cx));
+    Poll::Ready(Some(frame))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use futures_util::StreamExt;
+
+  use crate::test_utils::server_with_frames;
+  use crate::Frame;
+  use crate::OpCode;
+
+  #[tokio::test]
+  async fn stream_ends_after_close() {
+    let (mut server, mut client) = server_with_frames([
+      Frame::text(b"one"[..].into()),
+      Frame::close(1000, b""),
+    ])
+    .await;
+
+    assert_eq!(server.next().await.unwrap().unwrap().payload, b"one");
+    assert_eq!(server.next().await.unwrap().unwrap().opcode, OpCode::Close);
+    assert!(server.next().await.is_none());
+    assert_eq!(client.read_frame().await.unwrap().opcode, OpCode::Close);
+  }
+}
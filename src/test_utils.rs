@@ -0,0 +1,303 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for testing code built on fastwebsockets.
+
+use std::io::Cursor;
+use std::io::Read;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Instant;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+use crate::Frame;
+use crate::Payload;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+/// An in-memory stream that reads from pre-loaded data and records everything written to it.
+///
+/// Reads return EOF once the data is exhausted. Every operation completes immediately, which makes
+/// tests deterministic.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::test_utils::MockStream;
+/// use fastwebsockets::{Frame, OpCode, Role, WebSocket};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> anyhow::Result<()> {
+/// let input = Frame::text(b"hello"[..].into()).to_vec();
+/// let mut ws = WebSocket::after_handshake(MockStream::with_data(input), Role::Client);
+///
+/// let frame = ws.read_frame().await?;
+/// assert_eq!(frame.opcode, OpCode::Text);
+///
+/// ws.write_frame(Frame::text(b"hi"[..].into())).await?;
+/// assert!(!ws.into_inner().written().is_empty());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct MockStream {
+  read: Cursor<Vec<u8>>,
+  written: Vec<u8>,
+}
+
+impl MockStream {
+  /// Creates a `MockStream` that yields `data` when read.
+  pub fn with_data(data: impl Into<Vec<u8>>) -> Self {
+    Self {
+      read: Cursor::new(data.into()),
+      written: Vec::new(),
+    }
+  }
+
+  /// Returns everything that has been written to the stream.
+  pub fn written(&self) -> &[u8] {
+    &self.written
+  }
+}
+
+impl AsyncRead for MockStream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let n = self.get_mut().read.read(buf.initialize_unfilled())?;
+    buf.advance(n);
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl AsyncWrite for MockStream {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    self.get_mut().written.extend_from_slice(buf);
+    Poll::Ready(Ok(buf.len()))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+/// The direction of a [`RecordedFrame`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+  /// The frame was written to the peer.
+  Send,
+  /// The frame was read from the peer.
+  Receive,
+}
+
+/// A frame captured by a [`RecordingWebSocket`].
+///
+/// Payloads are recorded as the application sees them: received frames are already unmasked and
+/// sent frames are not masked yet.
+pub struct RecordedFrame {
+  pub direction: Direction,
+  pub timestamp: Instant,
+  pub frame: Frame<'static>,
+}
+
+impl Clone for RecordedFrame {
+  fn clone(&self) -> Self {
+    Self {
+      direction: self.direction,
+      timestamp: self.timestamp,
+      frame: copy_frame(&self.frame),
+    }
+  }
+}
+
+fn copy_frame(frame: &Frame) -> Frame<'static> {
+  Frame::new(
+    frame.fin,
+    frame.opcode,
+    frame.mask,
+    Payload::Owned(frame.payload.to_vec()),
+  )
+  .with_rsv(frame.rsv)
+}
+
+/// Wraps a [`WebSocket`] and records every frame read from or written to it.
+///
+/// Pair it with [`replay_from_recording`] to reproduce a session against a [`MockStream`].
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::test_utils::{replay_from_recording, Direction, MockStream, RecordingWebSocket};
+/// use fastwebsockets::{Frame, Role, WebSocket};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> anyhow::Result<()> {
+/// let input = Frame::text(b"hello"[..].into()).to_vec();
+/// let ws = WebSocket::after_handshake(MockStream::with_data(input), Role::Client);
+/// let mut ws = RecordingWebSocket::new(ws);
+///
+/// ws.read_frame().await?;
+/// ws.write_frame(Frame::text(b"hi"[..].into())).await?;
+///
+/// let recording = ws.save_recording();
+/// assert_eq!(recording[0].direction, Direction::Receive);
+/// assert_eq!(recording[1].direction, Direction::Send);
+///
+/// // Replaying yields the same frames again.
+/// let mut ws = WebSocket::after_handshake(replay_from_recording(&recording), Role::Client);
+/// assert_eq!(ws.read_frame().await?.payload, b"hello");
+/// # Ok(())
+/// # }
+/// ```
+pub struct RecordingWebSocket<S> {
+  ws: WebSocket<S>,
+  recording: Vec<RecordedFrame>,
+}
+
+impl<S> RecordingWebSocket<S> {
+  /// Starts recording the frames of `ws`.
+  pub fn new(ws: WebSocket<S>) -> Self {
+    Self {
+      ws,
+      recording: Vec::new(),
+    }
+  }
+
+  /// Returns a copy of the frames recorded so far, in the order they were read or written.
+  pub fn save_recording(&self) -> Vec<RecordedFrame> {
+    self.recording.clone()
+  }
+
+  /// Returns a reference to the wrapped `WebSocket`.
+  pub fn get_ref(&self) -> &WebSocket<S> {
+    &self.ws
+  }
+
+  /// Returns a mutable reference to the wrapped `WebSocket`. Frames read or written through it are
+  /// not recorded.
+  pub fn get_mut(&mut self) -> &mut WebSocket<S> {
+    &mut self.ws
+  }
+
+  /// Consumes the `RecordingWebSocket` and returns the wrapped `WebSocket`.
+  pub fn into_inner(self) -> WebSocket<S> {
+    self.ws
+  }
+
+  /// Reads a frame with [`WebSocket::read_frame`] and records it.
+  pub async fn read_frame(&mut self) -> Result<Frame<'static>, WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let frame = self.ws.read_frame().await?;
+    self.recording.push(RecordedFrame {
+      direction: Direction::Receive,
+      timestamp: Instant::now(),
+      frame: copy_frame(&frame),
+    });
+    Ok(frame)
+  }
+
+  /// Records a frame and writes it with [`WebSocket::write_frame`].
+  pub async fn write_frame(
+    &mut self,
+    frame: Frame<'_>,
+  ) -> Result<(), WebSocketError>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self.recording.push(RecordedFrame {
+      direction: Direction::Send,
+      timestamp: Instant::now(),
+      frame: copy_frame(&frame),
+    });
+    self.ws.write_frame(frame).await
+  }
+}
+
+/// Creates a [`MockStream`] that yields the received frames of a recording, so that the session can
+/// be read again. Sent frames are skipped, and masked frames are masked again with their original key.
+pub fn replay_from_recording(frames: &[RecordedFrame]) -> MockStream {
+  let mut data = Vec::new();
+  for recorded in frames {
+    if recorded.direction != Direction::Receive {
+      continue;
+    }
+    let mut frame = copy_frame(&recorded.frame);
+    if frame.mask.is_some() {
+      frame.mask();
+    }
+    data.extend_from_slice(&frame.to_vec());
+  }
+  MockStream::with_data(data)
+}
+
+/// Creates a connected server and client, and writes `frames` from the client so that they are the
+/// first frames the server reads.
+#[cfg(test)]
+pub(crate) async fn server_with_frames<'a>(
+  frames: impl IntoIterator<Item = Frame<'a>>,
+) -> (
+  WebSocket<tokio::io::DuplexStream>,
+  WebSocket<tokio::io::DuplexStream>,
+) {
+  let (server, mut client) =
+    WebSocket::in_memory_pair(crate::Role::Server, crate::Role::Client);
+  for frame in frames {
+    client.write_frame(frame).await.unwrap();
+  }
+  (server, client)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn replay_keeps_reserved_bits() {
+    let mut frame = Frame::binary(b"x"[..].into());
+    frame.set_rsv2(true);
+    let recorded = RecordedFrame {
+      direction: Direction::Receive,
+      timestamp: Instant::now(),
+      frame,
+    };
+    assert!(recorded.clone().frame.rsv2());
+
+    let mut expected = Frame::binary(b"x"[..].into());
+    expected.set_rsv2(true);
+    let data = replay_from_recording(&[recorded]).read.into_inner();
+    assert_eq!(data, expected.to_vec());
+  }
+}
@@ -0,0 +1,88 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio_rustls::client;
+use tokio_rustls::rustls::ServerName;
+use tokio_rustls::server;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::TlsConnector;
+
+use crate::ReadHalf;
+use crate::ReadState;
+use crate::WebSocket;
+use crate::WebSocketError;
+use crate::WriteHalf;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "tls")))]
+impl<S> WebSocket<S>
+where
+  S: AsyncRead + AsyncWrite + Unpin,
+{
+  /// Negotiates TLS as the client over the existing connection, e.g. after a STARTTLS-style
+  /// plaintext exchange, and returns a `WebSocket` over the encrypted stream with the same settings.
+  ///
+  /// Frames that are still queued are written in plaintext first. The peer must not send TLS data
+  /// before the plaintext exchange is complete: if bytes were already read ahead, they cannot be
+  /// handed to TLS and the upgrade fails with an `InvalidData` I/O error.
+  pub async fn upgrade_to_tls(
+    self,
+    connector: TlsConnector,
+    domain: ServerName,
+  ) -> Result<WebSocket<client::TlsStream<S>>, WebSocketError> {
+    let subprotocol = self.subprotocol.clone();
+    let (stream, read_half, write_half) = self.prepare_tls().await?;
+    Ok(WebSocket {
+      stream: connector.connect(domain, stream).await?,
+      read_half,
+      write_half,
+      subprotocol,
+    })
+  }
+
+  /// Negotiates TLS as the server over the existing connection. See [`WebSocket::upgrade_to_tls`].
+  pub async fn upgrade_to_tls_server(
+    self,
+    acceptor: TlsAcceptor,
+  ) -> Result<WebSocket<server::TlsStream<S>>, WebSocketError> {
+    let subprotocol = self.subprotocol.clone();
+    let (stream, read_half, write_half) = self.prepare_tls().await?;
+    Ok(WebSocket {
+      stream: acceptor.accept(stream).await?,
+      read_half,
+      write_half,
+      subprotocol,
+    })
+  }
+
+  /// Flushes queued frames and checks that nothing was read ahead of the TLS handshake.
+  async fn prepare_tls(
+    mut self,
+  ) -> Result<(S, ReadHalf, WriteHalf), WebSocketError> {
+    self.write_half.flush_pending(&mut self.stream).await?;
+    if !self.read_half.buffer.is_empty()
+      || !matches!(self.read_half.state, ReadState::Header)
+    {
+      return Err(
+        std::io::Error::new(
+          std::io::ErrorKind::InvalidData,
+          "data was received before the TLS handshake",
+        )
+        .into(),
+      );
+    }
+    Ok(self.into_parts_internal())
+  }
+}
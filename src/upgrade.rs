@@ -22,6 +22,7 @@ use base64;
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use http_body_util::Empty;
+use http_body_util::Full;
 use hyper::body::Bytes;
 use hyper::Request;
 use hyper::Response;
@@ -114,6 +115,12 @@ pub struct UpgradeFut {
   inner: hyper::upgrade::OnUpgrade,
 }
 
+impl UpgradeFut {
+  pub(crate) fn new(inner: hyper::upgrade::OnUpgrade) -> Self {
+    Self { inner }
+  }
+}
+
 /// Try to upgrade a received `hyper::Request` to a websocket connection.
 ///
 /// The function returns a HTTP response and a future that resolves to the websocket stream.
@@ -132,7 +139,65 @@ pub fn upgrade<B>(
   mut request: impl std::borrow::BorrowMut<Request<B>>,
 ) -> Result<(Response<Empty<Bytes>>, UpgradeFut), Error> {
   let request = request.borrow_mut();
+  let accept = accept_key(request)?;
+
+  let response = Response::builder()
+    .status(hyper::StatusCode::SWITCHING_PROTOCOLS)
+    .header(hyper::header::CONNECTION, "upgrade")
+    .header(hyper::header::UPGRADE, "websocket")
+    .header("Sec-WebSocket-Accept", &accept)
+    .body(Empty::new())
+    .expect("bug: failed to build response");
+
+  let stream = UpgradeFut {
+    inner: hyper::upgrade::on(request),
+  };
+
+  Ok((response, stream))
+}
+
+/// Like [`upgrade`], but also checks that the `Upgrade` header contains `websocket` and the
+/// `Connection` header contains `upgrade`, both case-insensitively, and answers invalid requests
+/// itself as RFC 6455 §4.2.1 asks.
+///
+/// A request failing any check gets a `400 Bad Request` instead of `101 Switching Protocols`; if the
+/// `Sec-WebSocket-Version` header is absent or not `13`, the response also carries
+/// `Sec-WebSocket-Version: 13` to tell the client which version is supported. As with
+/// [`upgrade_with_origin_validator`], the returned future then fails once the response has been sent.
+pub fn upgrade_strict<B>(
+  mut request: impl std::borrow::BorrowMut<Request<B>>,
+) -> Result<(Response<Empty<Bytes>>, UpgradeFut), Error> {
+  let request = request.borrow_mut();
+  let headers = request.headers();
+  let error =
+    if !header_contains_value(headers, hyper::header::UPGRADE, "websocket") {
+      WebSocketError::InvalidUpgradeHeader
+    } else if !header_contains_value(
+      headers,
+      hyper::header::CONNECTION,
+      "upgrade",
+    ) {
+      WebSocketError::InvalidConnectionHeader
+    } else {
+      match upgrade(&mut *request) {
+        Err(error) => error,
+        upgraded => return upgraded,
+      }
+    };
 
+  let mut response = Response::builder().status(hyper::StatusCode::BAD_REQUEST);
+  if matches!(error, WebSocketError::InvalidSecWebsocketVersion) {
+    response = response.header("Sec-WebSocket-Version", "13");
+  }
+  let response = response
+    .body(Empty::new())
+    .expect("bug: failed to build response");
+  Ok((response, UpgradeFut::new(hyper::upgrade::on(request))))
+}
+
+/// Checks the `Sec-WebSocket-Key` and `Sec-WebSocket-Version` headers of an upgrade request and
+/// returns the `Sec-WebSocket-Accept` value for the response.
+pub(crate) fn accept_key<B>(request: &Request<B>) -> Result<String, Error> {
   let key = request
     .headers()
     .get("Sec-WebSocket-Key")
@@ -145,23 +210,166 @@ pub fn upgrade<B>(
   {
     return Err(WebSocketError::InvalidSecWebsocketVersion);
   }
+  Ok(sec_websocket_protocol(key.as_bytes()))
+}
+
+/// Like [`upgrade`], but first passes the `Origin` header to `validator`, or an empty string if the
+/// header is absent or not valid UTF-8.
+///
+/// Browsers always send `Origin`, so checking it protects against cross-site WebSocket hijacking. If
+/// `validator` returns `false`, the returned response is `403 Forbidden` instead of
+/// `101 Switching Protocols`; the returned future then fails once the response has been sent, since
+/// the connection is not upgraded. Other validation errors are returned like in [`upgrade`].
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::upgrade;
+/// use http_body_util::Empty;
+/// use hyper::{body::{Bytes, Incoming}, Request, Response};
+///
+/// fn accept(mut req: Request<Incoming>) -> Result<Response<Empty<Bytes>>, fastwebsockets::WebSocketError> {
+///   let (response, fut) = upgrade::upgrade_with_origin_validator(&mut req, |origin| {
+///     origin == "https://example.com"
+///   })?;
+///   tokio::spawn(async move {
+///     if let Ok(ws) = fut.await {
+///       // Serve the connection
+///     }
+///   });
+///   Ok(response)
+/// }
+/// ```
+pub fn upgrade_with_origin_validator<B, F>(
+  mut request: impl std::borrow::BorrowMut<Request<B>>,
+  validator: F,
+) -> Result<(Response<Empty<Bytes>>, UpgradeFut), Error>
+where
+  F: Fn(&str) -> bool,
+{
+  let request = request.borrow_mut();
+  let origin = request
+    .headers()
+    .get(hyper::header::ORIGIN)
+    .and_then(|origin| origin.to_str().ok())
+    .unwrap_or("");
+  if validator(origin) {
+    return upgrade(request);
+  }
 
   let response = Response::builder()
-    .status(hyper::StatusCode::SWITCHING_PROTOCOLS)
-    .header(hyper::header::CONNECTION, "upgrade")
-    .header(hyper::header::UPGRADE, "websocket")
-    .header(
-      "Sec-WebSocket-Accept",
-      &sec_websocket_protocol(key.as_bytes()),
-    )
+    .status(hyper::StatusCode::FORBIDDEN)
     .body(Empty::new())
     .expect("bug: failed to build response");
+  Ok((response, UpgradeFut::new(hyper::upgrade::on(request))))
+}
 
-  let stream = UpgradeFut {
-    inner: hyper::upgrade::on(request),
-  };
+/// Serves HTTP/1.1 on `stream` until a websocket upgrade request arrives, like
+/// [`server_upgrade_with_fallback`], failing with [`WebSocketError::HandshakeTimeout`] if the whole
+/// handshake takes longer than `timeout`.
+///
+/// The timeout covers reading the request and writing the `101 Switching Protocols` response, so a
+/// client that trickles in the request line or headers is cut off too. On expiry `stream` is dropped,
+/// which closes the connection. Requests that are not websocket upgrades get a `400 Bad Request`.
+pub async fn server_upgrade_timeout<S>(
+  stream: S,
+  timeout: std::time::Duration,
+) -> Result<WebSocket<TokioIo<hyper::upgrade::Upgraded>>, Error>
+where
+  S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+  let upgrade = server_upgrade_with_fallback(stream, |_| async {
+    let mut response = Response::new(Full::new(Bytes::new()));
+    *response.status_mut() = hyper::StatusCode::BAD_REQUEST;
+    response
+  });
+  tokio::time::timeout(timeout, upgrade)
+    .await
+    .map_err(|_| WebSocketError::HandshakeTimeout)?
+}
 
-  Ok((response, stream))
+/// Serves HTTP/1.1 on `stream` until a websocket upgrade request arrives, and returns the upgraded websocket.
+///
+/// `GET` requests that pass [`is_upgrade_request`] are upgraded with [`upgrade`]; invalid upgrade
+/// requests get a `400 Bad Request`. Every other request is answered by `fallback`, which makes it
+/// possible to serve health checks and similar endpoints on the same port without a separate HTTP
+/// framework. Keep-alive is supported, so several fallback requests may be served before the upgrade.
+///
+/// Fails with [`WebSocketError::ConnectionClosed`] if the connection ends without an upgrade.
+///
+/// # Example
+///
+/// ```
+/// use fastwebsockets::upgrade::server_upgrade_with_fallback;
+/// use http_body_util::Full;
+/// use hyper::{body::Bytes, Response, StatusCode};
+/// use tokio::net::TcpStream;
+/// use anyhow::Result;
+///
+/// async fn handle(stream: TcpStream) -> Result<()> {
+///   let mut ws = server_upgrade_with_fallback(stream, |req| async move {
+///     let status = match req.uri().path() {
+///       "/health" => StatusCode::OK,
+///       _ => StatusCode::NOT_FOUND,
+///     };
+///     let mut response = Response::new(Full::new(Bytes::new()));
+///     *response.status_mut() = status;
+///     response
+///   })
+///   .await?;
+///   let frame = ws.read_frame().await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn server_upgrade_with_fallback<S, F, Fut>(
+  stream: S,
+  fallback: F,
+) -> Result<WebSocket<TokioIo<hyper::upgrade::Upgraded>>, Error>
+where
+  S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+  F: Fn(Request<hyper::body::Incoming>) -> Fut,
+  Fut: std::future::Future<Output = Response<Full<Bytes>>>,
+{
+  let pending = std::sync::Mutex::new(None);
+
+  let service = hyper::service::service_fn(|mut request| {
+    let response = if request.method() == hyper::Method::GET
+      && is_upgrade_request(&request)
+    {
+      Some(match upgrade(&mut request) {
+        Ok((response, fut)) => {
+          *pending.lock().unwrap() = Some(fut);
+          response.map(|_| Full::new(Bytes::new()))
+        }
+        Err(_) => {
+          let mut response = Response::new(Full::new(Bytes::new()));
+          *response.status_mut() = hyper::StatusCode::BAD_REQUEST;
+          response
+        }
+      })
+    } else {
+      None
+    };
+    let fallback = response.is_none().then(|| fallback(request));
+
+    async move {
+      Ok::<_, std::convert::Infallible>(match response {
+        Some(response) => response,
+        None => fallback.unwrap().await,
+      })
+    }
+  });
+
+  hyper::server::conn::http1::Builder::new()
+    .serve_connection(TokioIo::new(stream), service)
+    .with_upgrades()
+    .await?;
+
+  let fut = pending.into_inner().unwrap();
+  match fut {
+    Some(fut) => fut.await,
+    None => Err(WebSocketError::ConnectionClosed),
+  }
 }
 
 /// Check if a request is a websocket upgrade request.
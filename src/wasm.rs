@@ -0,0 +1,327 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::RefCell;
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+
+use bytes::Buf;
+use bytes::BytesMut;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+use web_sys::BinaryType;
+use web_sys::CloseEvent;
+use web_sys::Event;
+use web_sys::MessageEvent;
+
+use crate::Frame;
+use crate::OpCode;
+use crate::Role;
+use crate::WebSocket;
+use crate::WebSocketError;
+
+fn into_io_error(e: JsValue) -> std::io::Error {
+  std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e))
+}
+
+/// State shared with the browser event handlers.
+#[derive(Default)]
+struct Shared {
+  /// Received messages, encoded as unmasked frames.
+  incoming: BytesMut,
+  open: bool,
+  closed: bool,
+  failed: bool,
+  waker: Option<Waker>,
+}
+
+impl Shared {
+  fn push(&mut self, frame: Frame) {
+    self.incoming.extend_from_slice(&frame.to_vec());
+    self.wake();
+  }
+
+  fn wake(&mut self) {
+    if let Some(waker) = self.waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+/// A browser WebSocket (`web_sys::WebSocket`) exposed as a byte stream of WebSocket frames, so
+/// that it can be driven by [`WebSocket`] when compiled to WebAssembly.
+///
+/// The browser does the framing itself, so received messages are turned back into frames for
+/// reading, and frames written by the `WebSocket` are decoded and passed to the browser API. Pings
+/// and pongs are handled by the browser and cannot be sent or received. The browser only accepts
+/// close code 1000 or a code in the 3000-4999 range from scripts; a close frame with any other code
+/// closes the connection without one.
+///
+/// Create it with [`WebSocket::wasm_connect`].
+pub struct WasmStream {
+  socket: web_sys::WebSocket,
+  shared: Rc<RefCell<Shared>>,
+  /// Written bytes that do not form a complete frame yet.
+  outgoing: BytesMut,
+  /// Fragments of the message being written.
+  message: Vec<u8>,
+  message_opcode: OpCode,
+  _on_open: Closure<dyn FnMut()>,
+  _on_message: Closure<dyn FnMut(MessageEvent)>,
+  _on_close: Closure<dyn FnMut(CloseEvent)>,
+  _on_error: Closure<dyn FnMut(Event)>,
+}
+
+impl WasmStream {
+  fn connect(url: &str) -> Result<Self, WebSocketError> {
+    let socket = web_sys::WebSocket::new(url).map_err(into_io_error)?;
+    socket.set_binary_type(BinaryType::Arraybuffer);
+    let shared = Rc::new(RefCell::new(Shared::default()));
+
+    let state = shared.clone();
+    let on_open = Closure::<dyn FnMut()>::new(move || {
+      let mut state = state.borrow_mut();
+      state.open = true;
+      state.wake();
+    });
+
+    let state = shared.clone();
+    let on_message =
+      Closure::<dyn FnMut(MessageEvent)>::new(move |e: MessageEvent| {
+        let data = e.data();
+        let frame = if let Some(text) = data.as_string() {
+          Frame::text(text.into_bytes().into())
+        } else {
+          Frame::binary(js_sys::Uint8Array::new(&data).to_vec().into())
+        };
+        state.borrow_mut().push(frame);
+      });
+
+    let state = shared.clone();
+    let on_close =
+      Closure::<dyn FnMut(CloseEvent)>::new(move |e: CloseEvent| {
+        let mut state = state.borrow_mut();
+        match e.code() {
+          // 1005 means no status code was received and 1006 that the connection was lost, neither
+          // of which is sent on the wire.
+          1005 => state.push(Frame::close_raw(Vec::new().into())),
+          1006 => {}
+          code => state.push(Frame::close(code, e.reason().as_bytes())),
+        }
+        state.closed = true;
+        state.wake();
+      });
+
+    let state = shared.clone();
+    let on_error = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+      let mut state = state.borrow_mut();
+      state.failed = true;
+      state.wake();
+    });
+
+    socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+    Ok(Self {
+      socket,
+      shared,
+      outgoing: BytesMut::new(),
+      message: Vec::new(),
+      message_opcode: OpCode::Binary,
+      _on_open: on_open,
+      _on_message: on_message,
+      _on_close: on_close,
+      _on_error: on_error,
+    })
+  }
+
+  /// Returns the underlying browser WebSocket.
+  pub fn get_ref(&self) -> &web_sys::WebSocket {
+    &self.socket
+  }
+
+  /// Decodes the complete frames in `outgoing` and passes them to the browser.
+  fn send_frames(&mut self) -> std::io::Result<()> {
+    loop {
+      let buf = &self.outgoing[..];
+      if buf.len() < 2 {
+        return Ok(());
+      }
+      let fin = buf[0] & 0b10000000 != 0;
+      let opcode = OpCode::try_from(buf[0] & 0b00001111)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+      let masked = buf[1] & 0b10000000 != 0;
+      let (length, mut offset) = match buf[1] & 0b01111111 {
+        126 if buf.len() >= 4 => {
+          (u16::from_be_bytes([buf[2], buf[3]]) as usize, 4)
+        }
+        127 if buf.len() >= 10 => {
+          let mut length = [0; 8];
+          length.copy_from_slice(&buf[2..10]);
+          (u64::from_be_bytes(length) as usize, 10)
+        }
+        126 | 127 => return Ok(()),
+        length => (length as usize, 2),
+      };
+      let mask = if masked {
+        if buf.len() < offset + 4 {
+          return Ok(());
+        }
+        let mut mask = [0; 4];
+        mask.copy_from_slice(&buf[offset..offset + 4]);
+        offset += 4;
+        Some(mask)
+      } else {
+        None
+      };
+      if buf.len() < offset + length {
+        return Ok(());
+      }
+
+      self.outgoing.advance(offset);
+      let mut payload = self.outgoing.split_to(length);
+      if let Some(mask) = mask {
+        crate::mask::unmask(&mut payload, mask);
+      }
+
+      match opcode {
+        OpCode::Text | OpCode::Binary | OpCode::Continuation => {
+          if opcode != OpCode::Continuation {
+            self.message_opcode = opcode;
+          }
+          self.message.extend_from_slice(&payload);
+          if fin {
+            let message = std::mem::take(&mut self.message);
+            if self.message_opcode == OpCode::Text {
+              let text = String::from_utf8(message).map_err(|e| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+              })?;
+              self.socket.send_with_str(&text)
+            } else {
+              self.socket.send_with_u8_array(&message)
+            }
+            .map_err(into_io_error)?;
+          }
+        }
+        OpCode::Close => {
+          let code = (payload.len() >= 2)
+            .then(|| u16::from_be_bytes([payload[0], payload[1]]));
+          match code {
+            Some(code) if code == 1000 || (3000..5000).contains(&code) => {
+              let reason = String::from_utf8_lossy(&payload[2..]);
+              self.socket.close_with_code_and_reason(code, &reason)
+            }
+            _ => self.socket.close(),
+          }
+          .map_err(into_io_error)?;
+        }
+        // Ping and pong frames are handled by the browser.
+        _ => {}
+      }
+    }
+  }
+}
+
+impl Drop for WasmStream {
+  fn drop(&mut self) {
+    self.socket.set_onopen(None);
+    self.socket.set_onmessage(None);
+    self.socket.set_onclose(None);
+    self.socket.set_onerror(None);
+    let _ = self.socket.close();
+  }
+}
+
+impl AsyncRead for WasmStream {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    let mut shared = self.shared.borrow_mut();
+    if shared.incoming.is_empty() {
+      if shared.closed {
+        return Poll::Ready(Ok(()));
+      }
+      shared.waker = Some(cx.waker().clone());
+      return Poll::Pending;
+    }
+
+    let n = shared.incoming.len().min(buf.remaining());
+    buf.put_slice(&shared.incoming[..n]);
+    shared.incoming.advance(n);
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl AsyncWrite for WasmStream {
+  fn poll_write(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    let this = self.get_mut();
+    this.outgoing.extend_from_slice(buf);
+    this.send_frames()?;
+    Poll::Ready(Ok(buf.len()))
+  }
+
+  fn poll_flush(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    // Messages are handed to the browser as soon as their last frame is written.
+    Poll::Ready(Ok(()))
+  }
+
+  fn poll_shutdown(
+    self: Pin<&mut Self>,
+    _cx: &mut Context<'_>,
+  ) -> Poll<std::io::Result<()>> {
+    Poll::Ready(self.socket.close().map_err(into_io_error))
+  }
+}
+
+impl WebSocket<WasmStream> {
+  /// Opens a connection to `url` with the browser WebSocket API and waits until it is established.
+  ///
+  /// The handshake is done by the browser, so it cannot be customized beyond the URL. Once
+  /// connected, the `WebSocket` is used as any other client connection. See [`WasmStream`].
+  pub async fn wasm_connect(url: &str) -> Result<Self, WebSocketError> {
+    let stream = WasmStream::connect(url)?;
+    poll_fn(|cx| {
+      let mut shared = stream.shared.borrow_mut();
+      if shared.open {
+        Poll::Ready(Ok(()))
+      } else if shared.failed || shared.closed {
+        Poll::Ready(Err(WebSocketError::ConnectionClosed))
+      } else {
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    })
+    .await?;
+    Ok(Self::after_handshake(stream, Role::Client))
+  }
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "experimental")]
+
+use fastwebsockets::Frame;
+use fastwebsockets::OpCode;
+use fastwebsockets::Role;
+use fastwebsockets::WebSocket;
+use tokio::net::UdpSocket;
+
+#[tokio::test]
+async fn over_udp() {
+  let a = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+  let b = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+  a.connect(b.local_addr().unwrap()).await.unwrap();
+  b.connect(a.local_addr().unwrap()).await.unwrap();
+
+  let mut server = WebSocket::over_datagram(a, Role::Server);
+  let mut client = WebSocket::over_datagram(b, Role::Client);
+
+  let payload = vec![42; 4096];
+  client
+    .write_frame(Frame::binary(payload.clone().into()))
+    .await
+    .unwrap();
+  let frame = server.read_frame().await.unwrap();
+  assert_eq!(frame.opcode, OpCode::Binary);
+  assert_eq!(&frame.payload[..], &payload[..]);
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "h2")]
+
+use bytes::Bytes;
+use fastwebsockets::Frame;
+use fastwebsockets::OpCode;
+use fastwebsockets::Role;
+use fastwebsockets::WebSocket;
+use h2::ext::Protocol;
+
+#[tokio::test]
+async fn extended_connect() {
+  let (client_io, server_io) = tokio::io::duplex(64 << 10);
+
+  let server = tokio::spawn(async move {
+    let mut conn = h2::server::Builder::new()
+      .enable_connect_protocol()
+      .handshake::<_, Bytes>(server_io)
+      .await
+      .unwrap();
+    let (request, mut respond) = conn.accept().await.unwrap().unwrap();
+    tokio::spawn(async move { while conn.accept().await.is_some() {} });
+
+    assert_eq!(request.method(), http::Method::CONNECT);
+    let response = http::Response::new(());
+    let send = respond.send_response(response, false).unwrap();
+    let mut ws =
+      WebSocket::after_h2_upgrade(send, request.into_body(), Role::Server);
+
+    let frame = ws.read_frame().await.unwrap();
+    ws.write_frame(frame).await.unwrap();
+  });
+
+  let (client, conn) = h2::client::handshake(client_io).await.unwrap();
+  tokio::spawn(conn);
+  let mut client = client.ready().await.unwrap();
+
+  let mut request = http::Request::builder()
+    .method(http::Method::CONNECT)
+    .uri("http://localhost/chat")
+    .header("sec-websocket-version", "13")
+    .body(())
+    .unwrap();
+  request
+    .extensions_mut()
+    .insert(Protocol::from_static("websocket"));
+  let (response, send) = client.send_request(request, false).unwrap();
+  let response = response.await.unwrap();
+  assert_eq!(response.status(), http::StatusCode::OK);
+
+  let mut ws =
+    WebSocket::after_h2_upgrade(send, response.into_body(), Role::Client);
+  ws.write_frame(Frame::text(b"Hello over h2"[..].into()))
+    .await
+    .unwrap();
+  let frame = ws.read_frame().await.unwrap();
+  assert_eq!(frame.opcode, OpCode::Text);
+  assert_eq!(&frame.payload[..], b"Hello over h2");
+
+  server.await.unwrap();
+}
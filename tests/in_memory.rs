@@ -0,0 +1,56 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastwebsockets::Frame;
+use fastwebsockets::OpCode;
+use fastwebsockets::Role;
+use fastwebsockets::WebSocket;
+use fastwebsockets::WebSocketError;
+use tokio::io::DuplexStream;
+
+// The handler under test, written against a generic stream like a real server handler.
+async fn echo(mut ws: WebSocket<DuplexStream>) -> Result<(), WebSocketError> {
+  loop {
+    let frame = ws.read_frame().await?;
+    match frame.opcode {
+      OpCode::Close => break,
+      OpCode::Text | OpCode::Binary => {
+        ws.write_frame(frame).await?;
+      }
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+#[tokio::test]
+async fn echo_in_memory() {
+  let (server, mut client) =
+    WebSocket::in_memory_pair(Role::Server, Role::Client);
+  let handler = tokio::spawn(echo(server));
+
+  client
+    .write_frame(Frame::text(b"Hello!"[..].into()))
+    .await
+    .unwrap();
+  let frame = client.read_frame().await.unwrap();
+  assert_eq!(frame.opcode, OpCode::Text);
+  assert_eq!(&frame.payload[..], b"Hello!");
+
+  client.write_frame(Frame::close(1000, b"")).await.unwrap();
+  let frame = client.read_frame().await.unwrap();
+  assert_eq!(frame.opcode, OpCode::Close);
+
+  handler.await.unwrap().unwrap();
+}
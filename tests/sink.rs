@@ -0,0 +1,64 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use fastwebsockets::Frame;
+use fastwebsockets::OpCode;
+use fastwebsockets::Role;
+use fastwebsockets::WebSocket;
+use fastwebsockets::WebSocketError;
+use futures_sink::Sink;
+use std::future::poll_fn;
+use std::pin::Pin;
+
+async fn send<S>(sink: &mut S, frame: Frame<'_>) -> Result<(), WebSocketError>
+where
+  S: for<'f> Sink<Frame<'f>, Error = WebSocketError> + Unpin,
+{
+  poll_fn(|cx| Pin::new(&mut *sink).poll_ready(cx)).await?;
+  Pin::new(&mut *sink).start_send(frame)?;
+  poll_fn(|cx| Pin::new(&mut *sink).poll_flush(cx)).await
+}
+
+#[tokio::test]
+async fn sink_and_read_share_stream() {
+  let (client, server) = tokio::io::duplex(1024);
+  let (mut rx, mut tx) =
+    WebSocket::after_handshake(server, Role::Server).split_sink();
+  let mut client = WebSocket::after_handshake(client, Role::Client);
+
+  client
+    .write_frame(Frame::text(b"ping"[..].into()))
+    .await
+    .unwrap();
+  let frame = rx
+    .read_frame::<_, WebSocketError>(&mut |_| async { unreachable!() })
+    .await
+    .unwrap();
+  assert_eq!(frame.opcode, OpCode::Text);
+  assert_eq!(&frame.payload[..], b"ping");
+
+  send(&mut tx, Frame::binary(b"pong"[..].into()))
+    .await
+    .unwrap();
+  let frame = client.read_frame().await.unwrap();
+  assert_eq!(frame.opcode, OpCode::Binary);
+  assert_eq!(&frame.payload[..], b"pong");
+
+  send(&mut tx, Frame::close(1000, b"")).await.unwrap();
+  assert!(tx.is_closed());
+  assert!(matches!(
+    send(&mut tx, Frame::text(b"late"[..].into())).await,
+    Err(WebSocketError::ConnectionClosed)
+  ));
+}
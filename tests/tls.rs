@@ -0,0 +1,145 @@
+// Copyright 2023 Divy Srivastava <dj.srivastava23@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "tls")]
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use fastwebsockets::Frame;
+use fastwebsockets::OpCode;
+use fastwebsockets::Role;
+use fastwebsockets::WebSocket;
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::client::HandshakeSignatureValid;
+use tokio_rustls::rustls::client::ServerCertVerified;
+use tokio_rustls::rustls::client::ServerCertVerifier;
+use tokio_rustls::rustls::Certificate;
+use tokio_rustls::rustls::DigitallySignedStruct;
+use tokio_rustls::rustls::PrivateKey;
+use tokio_rustls::rustls::ServerName;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::TlsConnector;
+
+// The test certificate is self-signed, so the client accepts any certificate.
+struct NoVerify;
+
+impl ServerCertVerifier for NoVerify {
+  fn verify_server_cert(
+    &self,
+    _end_entity: &Certificate,
+    _intermediates: &[Certificate],
+    _server_name: &ServerName,
+    _scts: &mut dyn Iterator<Item = &[u8]>,
+    _ocsp_response: &[u8],
+    _now: SystemTime,
+  ) -> Result<ServerCertVerified, rustls::Error> {
+    Ok(ServerCertVerified::assertion())
+  }
+
+  // The certificate is not parsed for signature checks either, since webpki rejects v1 certificates.
+  fn verify_tls12_signature(
+    &self,
+    _message: &[u8],
+    _cert: &Certificate,
+    _dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    Ok(HandshakeSignatureValid::assertion())
+  }
+
+  fn verify_tls13_signature(
+    &self,
+    _message: &[u8],
+    _cert: &Certificate,
+    _dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    Ok(HandshakeSignatureValid::assertion())
+  }
+}
+
+fn tls_acceptor() -> TlsAcceptor {
+  static KEY: &[u8] = include_bytes!("../examples/localhost.key");
+  static CERT: &[u8] = include_bytes!("../examples/localhost.crt");
+
+  let key = rustls_pemfile::pkcs8_private_keys(&mut &*KEY)
+    .unwrap()
+    .remove(0);
+  let certs = rustls_pemfile::certs(&mut &*CERT)
+    .unwrap()
+    .into_iter()
+    .map(Certificate)
+    .collect();
+  let config = rustls::ServerConfig::builder()
+    .with_safe_defaults()
+    .with_no_client_auth()
+    .with_single_cert(certs, PrivateKey(key))
+    .unwrap();
+  TlsAcceptor::from(Arc::new(config))
+}
+
+fn tls_connector() -> TlsConnector {
+  let config = rustls::ClientConfig::builder()
+    .with_safe_defaults()
+    .with_custom_certificate_verifier(Arc::new(NoVerify))
+    .with_no_client_auth();
+  TlsConnector::from(Arc::new(config))
+}
+
+#[tokio::test]
+async fn upgrade_to_tls() {
+  let (mut server, mut client) =
+    WebSocket::in_memory_pair(Role::Server, Role::Client);
+  client.set_max_message_size(1024);
+
+  let server = tokio::spawn(async move {
+    let frame = server.read_frame().await.unwrap();
+    assert_eq!(frame.payload, b"STARTTLS");
+    server
+      .write_frame(Frame::text(b"OK"[..].into()))
+      .await
+      .unwrap();
+
+    let mut server =
+      server.upgrade_to_tls_server(tls_acceptor()).await.unwrap();
+    let frame = server.read_frame().await.unwrap();
+    assert_eq!(frame.payload, b"secret");
+    server.write_frame(Frame::close(1000, b"")).await.unwrap();
+    // Wait for the client to answer the close frame.
+    let frame = server.read_frame().await.unwrap();
+    assert_eq!(frame.opcode, OpCode::Close);
+  });
+
+  client
+    .write_frame(Frame::text(b"STARTTLS"[..].into()))
+    .await
+    .unwrap();
+  let frame = client.read_frame().await.unwrap();
+  assert_eq!(frame.payload, b"OK");
+
+  let domain = ServerName::try_from("localhost").unwrap();
+  let mut client = client
+    .upgrade_to_tls(tls_connector(), domain)
+    .await
+    .unwrap();
+  // Settings carry over to the encrypted connection.
+  assert_eq!(client.max_message_size(), 1024);
+  client
+    .write_frame(Frame::text(b"secret"[..].into()))
+    .await
+    .unwrap();
+  let frame = client.read_frame().await.unwrap();
+  assert_eq!(frame.opcode, OpCode::Close);
+
+  server.await.unwrap();
+}
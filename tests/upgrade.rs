@@ -87,6 +87,62 @@ async fn hyper() {
   assert!(close_frame.opcode == fastwebsockets::OpCode::Close);
 }
 
+#[tokio::test]
+async fn client_subprotocol() {
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(bind_addr) = listener.local_addr());
+
+  tokio::spawn(async move {
+    let (stream, _) = listener.accept().await.unwrap();
+    let service = service_fn(|mut request: Request<Incoming>| async move {
+      let (mut response, fut) = fastwebsockets::upgrade::upgrade(&mut request)?;
+      response
+        .headers_mut()
+        .insert("Sec-WebSocket-Protocol", "json".parse().unwrap());
+      tokio::spawn(async move {
+        let mut ws = fut.await.unwrap();
+        ws.set_subprotocol(Some("json".to_owned()));
+        assert!(ws.subprotocol() == Some("json"));
+        let _ = ws
+          .write_frame(fastwebsockets::Frame::text(b"{}".to_vec().into()))
+          .await;
+      });
+      Ok::<_, fastwebsockets::WebSocketError>(response)
+    });
+    let _ = http1::Builder::new()
+      .serve_connection(TokioIo::new(stream), service)
+      .with_upgrades()
+      .await;
+  });
+
+  let_assert!(Ok(stream) = TcpStream::connect(bind_addr).await);
+  let_assert!(
+    Ok(req) = Request::builder()
+      .method("GET")
+      .uri("ws://localhost/foo")
+      .header("Host", "localhost")
+      .header(UPGRADE, "websocket")
+      .header(CONNECTION, "upgrade")
+      .header(
+        "Sec-WebSocket-Key",
+        fastwebsockets::handshake::generate_key(),
+      )
+      .header("Sec-WebSocket-Version", "13")
+      .header("Sec-WebSocket-Protocol", "msgpack, json")
+      .body(Empty::<Bytes>::new())
+  );
+  let_assert!(
+    Ok((mut ws, _)) =
+      fastwebsockets::handshake::client(&TestExecutor, req, stream).await
+  );
+  assert!(ws.subprotocol() == Some("json"));
+  let_assert!(Ok(message) = ws.read_frame().await);
+  assert!(message.payload == b"{}");
+}
+
 async fn upgrade_websocket(
   mut request: Request<Incoming>,
 ) -> Result<Response<Empty<Bytes>>, fastwebsockets::WebSocketError> {
@@ -105,3 +161,495 @@ async fn upgrade_websocket(
 
   Ok(response)
 }
+
+#[tokio::test]
+async fn upgrade_with_fallback() {
+  use http_body_util::Full;
+  use tokio::io::AsyncReadExt;
+  use tokio::io::AsyncWriteExt;
+
+  async fn health(request: Request<Incoming>) -> Response<Full<Bytes>> {
+    assert!(request.uri().path() == "/health");
+    Response::new(Full::new(Bytes::from_static(b"ok")))
+  }
+
+  // A plain HTTP request is answered by the fallback.
+  let (mut client, server) = tokio::io::duplex(4096);
+  let server = tokio::spawn(
+    fastwebsockets::upgrade::server_upgrade_with_fallback(server, health),
+  );
+  let_assert!(
+    Ok(()) = client
+      .write_all(
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+      )
+      .await
+  );
+  let mut response = Vec::new();
+  let_assert!(Ok(_) = client.read_to_end(&mut response).await);
+  assert!(response.starts_with(b"HTTP/1.1 200 OK\r\n"));
+  assert!(response.ends_with(b"\r\n\r\nok"));
+  let_assert!(
+    Ok(Err(fastwebsockets::WebSocketError::ConnectionClosed)) = server.await
+  );
+
+  // An upgrade request is upgraded.
+  let (client, server) = tokio::io::duplex(4096);
+  let server = tokio::spawn(
+    fastwebsockets::upgrade::server_upgrade_with_fallback(server, health),
+  );
+  let_assert!(
+    Ok(req) = Request::builder()
+      .method("GET")
+      .uri("ws://localhost/")
+      .header("Host", "localhost")
+      .header(UPGRADE, "websocket")
+      .header(CONNECTION, "upgrade")
+      .header(
+        "Sec-WebSocket-Key",
+        fastwebsockets::handshake::generate_key(),
+      )
+      .header("Sec-WebSocket-Version", "13")
+      .body(Empty::<Bytes>::new())
+  );
+  let_assert!(Ok((mut client, _response)) = fastwebsockets::handshake::client(&TestExecutor, req, client).await);
+  let_assert!(Ok(Ok(mut server)) = server.await);
+
+  let_assert!(
+    Ok(()) = client
+      .write_frame(fastwebsockets::Frame::text(b"Hello!".to_vec().into()))
+      .await
+  );
+  let_assert!(Ok(message) = server.read_frame().await);
+  assert!(message.payload == b"Hello!");
+}
+
+#[tokio::test]
+async fn from_upgrade() {
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(bind_addr) = listener.local_addr());
+
+  tokio::spawn(async move {
+    let (stream, _) = listener.accept().await.unwrap();
+    let service = service_fn(|mut request: Request<Incoming>| async move {
+      // Take the pending upgrade first; the helper is only used to build the response.
+      let fut = fastwebsockets::handshake::from_upgrade(hyper::upgrade::on(
+        &mut request,
+      ));
+      let (response, _) = fastwebsockets::upgrade::upgrade(&mut request)?;
+      tokio::spawn(async move {
+        let_assert!(Ok(mut ws) = fut.await);
+        assert!(let Ok(()) = ws.write_frame(fastwebsockets::Frame::text(b"Hello!".to_vec().into())).await);
+      });
+      Ok::<_, fastwebsockets::WebSocketError>(response)
+    });
+    let _ = http1::Builder::new()
+      .serve_connection(TokioIo::new(stream), service)
+      .with_upgrades()
+      .await;
+  });
+
+  let_assert!(Ok(stream) = TcpStream::connect(bind_addr).await);
+  let_assert!(
+    Ok(req) = Request::builder()
+      .method("GET")
+      .uri("ws://localhost/foo")
+      .header("Host", "localhost")
+      .header(UPGRADE, "websocket")
+      .header(CONNECTION, "upgrade")
+      .header(
+        "Sec-WebSocket-Key",
+        fastwebsockets::handshake::generate_key(),
+      )
+      .header("Sec-WebSocket-Version", "13")
+      .body(Empty::<Bytes>::new())
+  );
+  let_assert!(Ok((mut ws, _)) = fastwebsockets::handshake::client(&TestExecutor, req, stream).await);
+  let_assert!(Ok(message) = ws.read_frame().await);
+  assert!(message.payload == b"Hello!");
+}
+
+#[cfg(feature = "tcp")]
+#[tokio::test]
+async fn connect_via_proxy() {
+  use tokio::io::AsyncReadExt;
+  use tokio::io::AsyncWriteExt;
+
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(proxy_addr) = listener.local_addr());
+
+  // A proxy that accepts the tunnel and then serves the WebSocket endpoint itself.
+  tokio::spawn(async move {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+      head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(head.starts_with("CONNECT example.com:80 HTTP/1.1\r\n"));
+    // "user:secret"
+    assert!(head.contains("Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+    stream
+      .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+      .await
+      .unwrap();
+
+    let _ = http1::Builder::new()
+      .serve_connection(TokioIo::new(stream), service_fn(upgrade_websocket))
+      .with_upgrades()
+      .await;
+  });
+
+  let_assert!(
+    Ok(mut ws) = fastwebsockets::handshake::connect_via_proxy(
+      proxy_addr,
+      "ws://example.com/foo",
+      Some(("user", "secret")),
+    )
+    .await
+  );
+  let_assert!(Ok(message) = ws.read_frame().await);
+  assert!(message.payload == b"Hello!");
+  let_assert!(
+    Ok(()) = ws
+      .write_frame(fastwebsockets::Frame::text(b"Goodbye!".to_vec().into()))
+      .await
+  );
+  let_assert!(Ok(close_frame) = ws.read_frame().await);
+  assert!(close_frame.opcode == fastwebsockets::OpCode::Close);
+}
+
+#[cfg(feature = "tcp")]
+#[tokio::test]
+async fn retrying_client() {
+  use std::sync::atomic::AtomicU32;
+  use std::sync::atomic::Ordering;
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(bind_addr) = listener.local_addr());
+
+  // The first two connections are dropped before the handshake completes.
+  tokio::spawn(async move {
+    for _ in 0..2 {
+      drop(listener.accept().await.unwrap());
+    }
+    let (stream, _) = listener.accept().await.unwrap();
+    let _ = http1::Builder::new()
+      .serve_connection(TokioIo::new(stream), service_fn(upgrade_websocket))
+      .with_upgrades()
+      .await;
+  });
+
+  let retries = Arc::new(AtomicU32::new(0));
+  let mut client = fastwebsockets::handshake::RetryingWebSocketClient::new(
+    format!("ws://{}/foo", bind_addr),
+  );
+  client.set_backoff(Duration::from_millis(1), Duration::from_millis(3), 2.0);
+  let counter = retries.clone();
+  client.set_on_retry(move |attempt, delay| {
+    counter.fetch_add(1, Ordering::SeqCst);
+    assert!(
+      delay
+        == Duration::from_millis(1 << (attempt - 1))
+          .min(Duration::from_millis(3))
+    );
+  });
+
+  let_assert!(Ok(mut ws) = client.connect().await);
+  assert!(retries.load(Ordering::SeqCst) == 2);
+  let_assert!(Ok(message) = ws.read_frame().await);
+  assert!(message.payload == b"Hello!");
+
+  // Retries are exhausted when nothing listens on the port.
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(closed_addr) = listener.local_addr());
+  drop(listener);
+  let retries = Arc::new(AtomicU32::new(0));
+  let mut client = fastwebsockets::handshake::RetryingWebSocketClient::new(
+    format!("ws://{}/foo", closed_addr),
+  );
+  client.set_max_retries(3);
+  client.set_backoff(Duration::from_millis(1), Duration::from_millis(3), 2.0);
+  let counter = retries.clone();
+  client.set_on_retry(move |_, _| {
+    counter.fetch_add(1, Ordering::SeqCst);
+  });
+  assert!(client.connect().await.is_err());
+  assert!(retries.load(Ordering::SeqCst) == 3);
+}
+
+#[cfg(feature = "tcp")]
+#[tokio::test]
+async fn retrying_client_backoff_overflow() {
+  use std::sync::Arc;
+  use std::sync::Mutex;
+  use std::time::Duration;
+
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(closed_addr) = listener.local_addr());
+  drop(listener);
+
+  let delays = Arc::new(Mutex::new(Vec::new()));
+  let mut client = fastwebsockets::handshake::RetryingWebSocketClient::new(
+    format!("ws://{}/foo", closed_addr),
+  );
+  client.set_max_retries(3);
+  // The delay overflows after the first retry and is capped instead.
+  client.set_backoff(
+    Duration::from_millis(1),
+    Duration::from_millis(3),
+    f64::MAX,
+  );
+  let recorded = delays.clone();
+  client.set_on_retry(move |_, delay| recorded.lock().unwrap().push(delay));
+  assert!(client.connect().await.is_err());
+  assert!(
+    *delays.lock().unwrap() == [1, 3, 3].map(Duration::from_millis).to_vec()
+  );
+}
+
+#[cfg(feature = "tcp")]
+#[test]
+#[should_panic(expected = "backoff factor")]
+fn retrying_client_invalid_backoff() {
+  let mut client = fastwebsockets::handshake::RetryingWebSocketClient::new(
+    "ws://localhost/foo",
+  );
+  client.set_backoff(
+    std::time::Duration::from_millis(1),
+    std::time::Duration::from_secs(1),
+    f64::NAN,
+  );
+}
+
+#[tokio::test]
+async fn upgrade_with_origin_validator() {
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(bind_addr) = listener.local_addr());
+
+  tokio::spawn(async move {
+    loop {
+      let (stream, _) = listener.accept().await.unwrap();
+      let service = service_fn(|mut request: Request<Incoming>| async move {
+        let (response, fut) =
+          fastwebsockets::upgrade::upgrade_with_origin_validator(
+            &mut request,
+            |origin| origin == "https://good.example",
+          )?;
+        tokio::spawn(async move {
+          if let Ok(mut ws) = fut.await {
+            let _ = ws
+              .write_frame(fastwebsockets::Frame::text(
+                b"Hello!".to_vec().into(),
+              ))
+              .await;
+          }
+        });
+        Ok::<_, fastwebsockets::WebSocketError>(response)
+      });
+      tokio::spawn(
+        http1::Builder::new()
+          .serve_connection(TokioIo::new(stream), service)
+          .with_upgrades(),
+      );
+    }
+  });
+
+  for (origin, accepted) in [
+    ("https://good.example", true),
+    ("https://evil.example", false),
+  ] {
+    let_assert!(Ok(stream) = TcpStream::connect(bind_addr).await);
+    let_assert!(
+      Ok(req) = Request::builder()
+        .method("GET")
+        .uri("ws://localhost/foo")
+        .header("Host", "localhost")
+        .header("Origin", origin)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "upgrade")
+        .header(
+          "Sec-WebSocket-Key",
+          fastwebsockets::handshake::generate_key(),
+        )
+        .header("Sec-WebSocket-Version", "13")
+        .body(Empty::<Bytes>::new())
+    );
+    let res =
+      fastwebsockets::handshake::client(&TestExecutor, req, stream).await;
+    if accepted {
+      let_assert!(Ok((mut ws, _)) = res);
+      let_assert!(Ok(message) = ws.read_frame().await);
+      assert!(message.payload == b"Hello!");
+    } else {
+      let_assert!(
+        Err(fastwebsockets::WebSocketError::InvalidStatusCode(403)) = res
+      );
+    }
+  }
+}
+
+#[tokio::test]
+async fn server_upgrade_timeout() {
+  use tokio::io::AsyncWriteExt;
+
+  let (mut client, server) = tokio::io::duplex(1024);
+  let upgrade = tokio::spawn(fastwebsockets::upgrade::server_upgrade_timeout(
+    server,
+    std::time::Duration::from_millis(100),
+  ));
+  // A request line that never ends.
+  let_assert!(
+    Ok(()) = client.write_all(b"GET /foo HTTP/1.1\r\nHost: lo").await
+  );
+  let_assert!(
+    Ok(Err(fastwebsockets::WebSocketError::HandshakeTimeout)) = upgrade.await
+  );
+
+  let (client, server) = tokio::io::duplex(1024);
+  let upgrade = tokio::spawn(fastwebsockets::upgrade::server_upgrade_timeout(
+    server,
+    std::time::Duration::from_secs(10),
+  ));
+  let_assert!(
+    Ok(req) = Request::builder()
+      .method("GET")
+      .uri("ws://localhost/foo")
+      .header("Host", "localhost")
+      .header(UPGRADE, "websocket")
+      .header(CONNECTION, "upgrade")
+      .header(
+        "Sec-WebSocket-Key",
+        fastwebsockets::handshake::generate_key(),
+      )
+      .header("Sec-WebSocket-Version", "13")
+      .body(Empty::<Bytes>::new())
+  );
+  let_assert!(
+    Ok(_) = fastwebsockets::handshake::client(&TestExecutor, req, client).await
+  );
+  let_assert!(Ok(Ok(_)) = upgrade.await);
+}
+
+#[test]
+fn upgrade_strict() {
+  let valid = || {
+    Request::builder()
+      .method("GET")
+      .uri("ws://localhost/foo")
+      .header(UPGRADE, "WebSocket")
+      .header(CONNECTION, "keep-alive, Upgrade")
+      .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+      .header("Sec-WebSocket-Version", "13")
+  };
+  let_assert!(Ok(request) = valid().body(Empty::<Bytes>::new()));
+  let_assert!(
+    Ok((response, _)) = fastwebsockets::upgrade::upgrade_strict(request)
+  );
+  assert!(response.status() == 101);
+  assert!(
+    response.headers()["Sec-WebSocket-Accept"]
+      == "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+  );
+
+  for (header, value, version_hint) in [
+    (UPGRADE.as_str(), None, false),
+    (UPGRADE.as_str(), Some("h2c"), false),
+    (CONNECTION.as_str(), Some("close"), false),
+    ("Sec-WebSocket-Version", None, true),
+    ("Sec-WebSocket-Version", Some("8"), true),
+  ] {
+    let_assert!(Ok(mut request) = valid().body(Empty::<Bytes>::new()));
+    match value {
+      Some(value) => {
+        request
+          .headers_mut()
+          .insert(header, hyper::header::HeaderValue::from_static(value));
+      }
+      None => {
+        request.headers_mut().remove(header);
+      }
+    }
+    let_assert!(
+      Ok((response, _)) = fastwebsockets::upgrade::upgrade_strict(request)
+    );
+    assert!(response.status() == 400);
+    assert!(
+      response.headers().get("Sec-WebSocket-Version").is_some() == version_hint
+    );
+  }
+}
+
+#[tokio::test]
+async fn from_parts() {
+  let_assert!(
+    Ok(listener) =
+      tokio::net::TcpListener::bind((Ipv6Addr::LOCALHOST, 0u16)).await
+  );
+  let_assert!(Ok(bind_addr) = listener.local_addr());
+
+  tokio::spawn(async move {
+    use tokio::io::AsyncReadExt;
+
+    // Stands in for a framework that parses the request head itself.
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+      head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap();
+    let mut request = Request::builder().method("GET").uri("/foo");
+    for line in head.lines().skip(1).filter(|line| !line.is_empty()) {
+      let (name, value) = line.split_once(": ").unwrap();
+      request = request.header(name, value);
+    }
+
+    let_assert!(
+      Ok(mut ws) = fastwebsockets::handshake::from_parts(
+        request.body(()).unwrap(),
+        stream
+      )
+      .await
+    );
+    assert!(let Ok(()) = ws.write_frame(fastwebsockets::Frame::text(b"Hello!".to_vec().into())).await);
+  });
+
+  let_assert!(Ok(stream) = TcpStream::connect(bind_addr).await);
+  let_assert!(
+    Ok(req) = Request::builder()
+      .method("GET")
+      .uri("ws://localhost/foo")
+      .header("Host", "localhost")
+      .header(UPGRADE, "websocket")
+      .header(CONNECTION, "upgrade")
+      .header(
+        "Sec-WebSocket-Key",
+        fastwebsockets::handshake::generate_key(),
+      )
+      .header("Sec-WebSocket-Version", "13")
+      .body(Empty::<Bytes>::new())
+  );
+  let_assert!(Ok((mut ws, _)) = fastwebsockets::handshake::client(&TestExecutor, req, stream).await);
+  let_assert!(Ok(message) = ws.read_frame().await);
+  assert!(message.payload == b"Hello!");
+}